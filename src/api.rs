@@ -1,31 +1,79 @@
-use std::fs::File;
-use std::io::Write;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
 
+use crate::bulk::BulkJob;
+use crate::crawler::{CrawlResult, MatchIdCrawler};
+use crate::endpoints::{GetGods, GetItems};
+use crate::hi_rez_constants::{DataConstants, Language, ReturnDataType};
 use crate::models::{PlayerMatchDetails, God, Item};
 use crate::request_maker::{GetMatchIdsByQueueRequest, RequestMaker};
-use crate::hi_rez_constants::UrlConstants;
 
 cfg_if::cfg_if! {
     if #[cfg(test)] {
+        use galvanic_test::test_suite;
     } else {
+        use crate::hi_rez_constants::UrlConstants;
+        use crate::credential_pool::{CredentialPool, PoolStrategy};
         use crate::session_manager::{Auth, SessionManager};
     }
 }
 
+// each entry in every language it was retrieved in, keyed by god or item id
+pub type LocalizedCatalog<T> = BTreeMap<u16, HashMap<Language, T>>;
+
 pub struct SmiteAPI {
     request_maker: RequestMaker,
 }
 
 impl SmiteAPI {
+    /*
+     * Loads credentials from the environment if set, otherwise from the file at path_to_creds
+     */
     #[cfg(not(test))]
-    pub fn new(path_to_creds: String) -> SmiteAPI {
-        let auth = Auth::from_file(&path_to_creds);
+    pub fn new(path_to_creds: String) -> Result<SmiteAPI, String> {
+        let auth = Auth::load(&path_to_creds)?;
         let session_manager = SessionManager::new(auth, UrlConstants::UrlBase);
-        SmiteAPI {
+        Ok(SmiteAPI {
             request_maker: RequestMaker::new(session_manager),
+        })
+    }
+
+    /*
+     * Uses the sessions of a hirust-broker listening on socket_path instead of creating our own
+     */
+    #[cfg(all(unix, not(test)))]
+    pub fn brokered(socket_path: String) -> SmiteAPI {
+        let session_provider = crate::broker::BrokeredSessionManager::new(socket_path);
+        SmiteAPI {
+            request_maker: RequestMaker::with_provider(std::sync::Arc::new(session_provider)),
         }
     }
 
+    /*
+     * Spreads requests across several dev ids, reading one credentials file per dev id
+     */
+    #[cfg(not(test))]
+    pub fn with_credential_pool(
+        paths_to_creds: Vec<String>,
+        strategy: PoolStrategy,
+    ) -> Result<SmiteAPI, String> {
+        let mut credentials = Vec::new();
+        for path in paths_to_creds {
+            credentials.push(Auth::load(&path)?);
+        }
+        let pool = CredentialPool::new(credentials, UrlConstants::UrlBase, strategy)?;
+        Ok(SmiteAPI {
+            request_maker: RequestMaker::with_provider(std::sync::Arc::new(pool)),
+        })
+    }
+
+    /*
+     * Requests replies as JSON or XML, which needs the xml feature
+     */
+    pub fn set_data_type(&mut self, data_type: ReturnDataType) -> Result<(), String> {
+        self.request_maker.set_data_type(data_type)
+    }
+
     pub fn get_match_ids_by_queue(
         &mut self,
         requests: Vec<GetMatchIdsByQueueRequest>,
@@ -33,6 +81,35 @@ impl SmiteAPI {
         self.request_maker.get_match_ids_by_queue(requests)
     }
 
+    /*
+     * Retrieves the ids of every completed match in queues from start up to end, splitting any
+     * windows HiRez returns nothing for
+     */
+    pub fn crawl_match_ids(
+        &self,
+        queues: Vec<DataConstants>,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> CrawlResult {
+        MatchIdCrawler::new(&self.request_maker)
+            .split_empty(true)
+            .crawl(queues, start, end)
+    }
+
+    /*
+     * Opens a resumable pull of every match in queues from start up to end, checkpointed to
+     * checkpoint_path
+     */
+    pub fn bulk_job(
+        &self,
+        checkpoint_path: &str,
+        queues: Vec<DataConstants>,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<BulkJob<'_>, String> {
+        BulkJob::open(&self.request_maker, checkpoint_path, queues, start, end)
+    }
+
     pub fn get_match_details(
         &self,
         match_ids: Vec<String>,
@@ -40,35 +117,92 @@ impl SmiteAPI {
         self.request_maker.get_match_details(match_ids)
     }
 
-    pub fn get_gods(&self) -> Vec<God> {
-        let gods_str = self.request_maker.concurrent_reqwest(UrlConstants::GetGods, vec![String::from("/1")]);
-        let gods: Vec<God> = match serde_json::from_str(&gods_str[0]) {
-            Ok(json) => json,
-            Err(msg) => {
-                let mut file = File::create("debug_dump.json").unwrap();
-                file.write_all(gods_str[0].as_bytes()).unwrap();
-                panic!(
-                    "Error deserializing get gods: {}",
-                    msg
-                );
-            }
-        };
-        gods
-    }
-
-    pub fn get_items(&self) -> Vec<Item> {
-        let items_str = self.request_maker.concurrent_reqwest(UrlConstants::GetItems, vec![String::from("/1")]);
-        let items: Vec<Item> = match serde_json::from_str(&items_str[0]) {
-            Ok(json) => json,
-            Err(msg) => {
-                let mut file = File::create("debug_dump.json").unwrap();
-                file.write_all(items_str[0].as_bytes()).unwrap();
-                panic!(
-                    "Error deserializing get items: {}",
-                    msg
-                );
+    pub fn get_gods(&self, language: Language) -> Vec<God> {
+        match self.request_maker.execute(vec![GetGods { language }]).remove(0) {
+            Ok(gods) => gods,
+            Err(msg) => panic!("Error getting gods: {}", msg),
+        }
+    }
+
+    pub fn get_items(&self, language: Language) -> Vec<Item> {
+        match self.request_maker.execute(vec![GetItems { language }]).remove(0) {
+            Ok(items) => items,
+            Err(msg) => panic!("Error getting items: {}", msg),
+        }
+    }
+
+    /*
+     * Retrieves the gods in each language, keyed by god id
+     */
+    pub fn get_localized_gods(&self, languages: Vec<Language>) -> Result<LocalizedCatalog<God>, String> {
+        let requests = languages.iter().map(|&language| GetGods { language }).collect();
+        let mut catalogs = Vec::new();
+        for (language, reply) in languages.into_iter().zip(self.request_maker.execute(requests)) {
+            catalogs.push((language, reply?));
+        }
+        Ok(merge_languages(catalogs, |x| x.id))
+    }
+
+    /*
+     * Retrieves the items in each language, keyed by item id
+     */
+    pub fn get_localized_items(&self, languages: Vec<Language>) -> Result<LocalizedCatalog<Item>, String> {
+        let requests = languages.iter().map(|&language| GetItems { language }).collect();
+        let mut catalogs = Vec::new();
+        for (language, reply) in languages.into_iter().zip(self.request_maker.execute(requests)) {
+            catalogs.push((language, reply?));
+        }
+        Ok(merge_languages(catalogs, |x| x.ItemId))
+    }
+}
+
+/*
+ * Combines catalogs retrieved in several languages into one keyed by id, skipping entries without one
+ */
+pub fn merge_languages<T, F>(catalogs: Vec<(Language, Vec<T>)>, id: F) -> LocalizedCatalog<T>
+where
+    F: Fn(&T) -> Option<u16>,
+{
+    let mut merged: LocalizedCatalog<T> = BTreeMap::new();
+    for (language, catalog) in catalogs {
+        for entry in catalog {
+            if let Some(id) = id(&entry) {
+                merged.entry(id).or_default().insert(language, entry);
             }
-        };
-        items
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+test_suite! {
+    name test_api;
+    use super::*;
+
+    fn item(id: u16, name: &str) -> Item {
+        Item {
+            DeviceName: String::from(name),
+            ItemId: Some(id),
+            ItemTier: None,
+            Price: None,
+            RestrictedRoles: None,
+            Type: None,
+            ret_msg: None,
+        }
+    }
+
+    test merge_languages_by_id() {
+        let mut unnumbered = item(0, "Unnumbered");
+        unnumbered.ItemId = None;
+        let catalogs = vec![
+            (Language::English, vec![item(7, "Boots"), item(9, "Sword"), unnumbered]),
+            (Language::German, vec![item(9, "Schwert"), item(7, "Stiefel")]),
+        ];
+
+        let merged = merge_languages(catalogs, |x| x.ItemId);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[&7][&Language::English].DeviceName, "Boots");
+        assert_eq!(merged[&7][&Language::German].DeviceName, "Stiefel");
+        assert_eq!(merged[&9][&Language::German].DeviceName, "Schwert");
     }
 }
@@ -0,0 +1,241 @@
+use chrono::{DateTime, Utc};
+use crypto::{digest::Digest, sha2::Sha256};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::clock::{Clock, SystemClock};
+use crate::decode::decode;
+use crate::hi_rez_constants::ReturnDataType;
+use crate::session_store::write_atomically;
+
+#[cfg(test)]
+use galvanic_test::test_suite;
+
+const INDEX_FILE: &str = "index.ndjson";
+
+// each archived response with the result of parsing it again
+pub type Reprocessed<T> = Vec<(ArchiveEntry, Result<T, String>)>;
+
+// one response, as recorded in the archive's index
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ArchiveEntry {
+    pub method_name: String,
+    // the path parameters the method was called with, e.g. /956598608
+    pub params: String,
+    pub data_type: ReturnDataType,
+    pub fetched_at: DateTime<Utc>,
+    // the SHA-256 of the body, which names the file it is stored in
+    pub digest: String,
+}
+
+/*
+ * Keeps the body of every response so it can be parsed again without spending requests,
+ * for instance after the models are updated to match a change to the API
+ *
+ * Bodies are gzipped and stored once per distinct content, while the index records every
+ * method, parameters, and fetch time they were returned for
+ */
+pub struct ResponseArchive {
+    dir: PathBuf,
+    clock: Box<dyn Clock>,
+    // serializes appends to the index between request threads
+    index_lock: Mutex<()>,
+}
+
+impl ResponseArchive {
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<ResponseArchive, String> {
+        ResponseArchive::with_clock(dir, Box::new(SystemClock))
+    }
+
+    pub fn with_clock<P: AsRef<Path>>(
+        dir: P,
+        clock: Box<dyn Clock>,
+    ) -> Result<ResponseArchive, String> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)
+            .map_err(|msg| format!("Error creating {}: {}", dir.display(), msg))?;
+        Ok(ResponseArchive {
+            dir,
+            clock,
+            index_lock: Mutex::new(()),
+        })
+    }
+
+    fn body_path(&self, digest: &str) -> PathBuf {
+        self.dir
+            .join("objects")
+            .join(&digest[..2])
+            .join(format!("{}.gz", &digest[2..]))
+    }
+
+    /*
+     * Stores body, returning the entry added to the index
+     */
+    pub fn store(
+        &self,
+        method_name: &str,
+        params: &str,
+        data_type: &ReturnDataType,
+        body: &str,
+    ) -> Result<ArchiveEntry, String> {
+        let mut sha = Sha256::new();
+        sha.input_str(body);
+        let entry = ArchiveEntry {
+            method_name: String::from(method_name),
+            params: String::from(params),
+            data_type: data_type.clone(),
+            fetched_at: self.clock.now(),
+            digest: sha.result_str(),
+        };
+
+        let path = self.body_path(&entry.digest);
+        if !path.exists() {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder
+                .write_all(body.as_bytes())
+                .map_err(|msg| msg.to_string())?;
+            let compressed = encoder.finish().map_err(|msg| msg.to_string())?;
+
+            fs::create_dir_all(path.parent().unwrap()).map_err(|msg| msg.to_string())?;
+            write_atomically(&path, &compressed)
+                .map_err(|msg| format!("Error writing {}: {}", path.display(), msg))?;
+        }
+
+        let mut line = serde_json::to_string(&entry).map_err(|msg| msg.to_string())?;
+        line.push('\n');
+        let _guard = self.index_lock.lock().unwrap();
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(INDEX_FILE))
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|msg| format!("Error updating archive index: {}", msg))?;
+
+        Ok(entry)
+    }
+
+    // every response stored, oldest first
+    pub fn entries(&self) -> Result<Vec<ArchiveEntry>, String> {
+        let file = match File::open(self.dir.join(INDEX_FILE)) {
+            Ok(file) => file,
+            // nothing has been stored yet
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(msg) => return Err(format!("Error opening archive index: {}", msg)),
+        };
+
+        let mut entries = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|msg| msg.to_string())?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            entries.push(
+                serde_json::from_str(&line)
+                    .map_err(|msg| format!("Invalid archive index entry: {}", msg))?,
+            );
+        }
+        Ok(entries)
+    }
+
+    pub fn body(&self, entry: &ArchiveEntry) -> Result<String, String> {
+        let path = self.body_path(&entry.digest);
+        let file =
+            File::open(&path).map_err(|msg| format!("Error opening {}: {}", path.display(), msg))?;
+        let mut body = String::new();
+        GzDecoder::new(file)
+            .read_to_string(&mut body)
+            .map_err(|msg| format!("Error reading {}: {}", path.display(), msg))?;
+        Ok(body)
+    }
+
+    /*
+     * Parses every stored response to method_name again with the current models
+     */
+    pub fn reprocess<T: DeserializeOwned>(
+        &self,
+        method_name: &str,
+    ) -> Result<Reprocessed<T>, String> {
+        let mut replies = Vec::new();
+        for entry in self.entries()? {
+            if entry.method_name != method_name {
+                continue;
+            }
+            let reply = self.body(&entry).and_then(|body| {
+                decode(&entry.data_type, &body).map_err(|msg| {
+                    format!("Error deserializing {} reply: {}", method_name, msg)
+                })
+            });
+            replies.push((entry, reply));
+        }
+        Ok(replies)
+    }
+}
+
+#[cfg(test)]
+test_suite! {
+    name test_archive;
+    use super::*;
+    use chrono::TimeZone;
+    use std::env;
+    use std::sync::Arc;
+    use crate::clock::FixedClock;
+    use crate::endpoints::GetMatchDetailsBatch;
+    use crate::hi_rez_constants::UrlConstants;
+    use crate::models::PlayerMatchDetails;
+    use crate::request_maker::RequestMaker;
+    use crate::reqwest_wrapper::MockWrapper as ReqwestWrapper;
+    use crate::test_responses;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("hirust-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    test identical_bodies_are_stored_once() {
+        let dir = temp_dir("archive-store");
+        let clock = Arc::new(FixedClock::new(Utc.with_ymd_and_hms(2019, 8, 5, 3, 5, 0).unwrap()));
+        let archive = ResponseArchive::with_clock(&dir, Box::new(Arc::clone(&clock))).unwrap();
+
+        let first = archive.store("getgods", "/1", &ReturnDataType::Json, "[]").unwrap();
+        clock.set(Utc.with_ymd_and_hms(2019, 8, 6, 3, 5, 0).unwrap());
+        let second = archive.store("getgods", "/1", &ReturnDataType::Json, "[]").unwrap();
+
+        assert_eq!(first.digest, second.digest);
+        assert_eq!(archive.entries().unwrap(), vec![first.clone(), second]);
+        assert_eq!(archive.body(&first).unwrap(), "[]");
+        let objects: usize = fs::read_dir(dir.join("objects")).unwrap().count();
+        assert_eq!(objects, 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    test requests_are_archived_and_reprocessed() {
+        let dir = temp_dir("archive-requests");
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest
+            .expect_get_to_text()
+            .times(1)
+            .returning(|_x| Ok(String::from(test_responses::GET_MATCH_DETAILS)));
+        let mut request_maker = RequestMaker::mock(reqwest);
+        request_maker.set_archive(ResponseArchive::open(&dir).unwrap());
+
+        request_maker.execute(vec![GetMatchDetailsBatch { match_ids: vec![String::from("956598608")] }]);
+
+        let replies = request_maker
+            .archive()
+            .unwrap()
+            .reprocess::<Vec<PlayerMatchDetails>>(&UrlConstants::GetMatchDetailsBatch.val())
+            .unwrap();
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].0.params, "/956598608");
+        assert_eq!(replies[0].1.as_ref().unwrap().len(), 10);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
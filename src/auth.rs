@@ -0,0 +1,243 @@
+use serde::Deserialize;
+use serde_json;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+#[cfg(test)]
+use galvanic_test::test_suite;
+
+pub const DEV_ID_VAR: &str = "HIREZ_DEV_ID";
+pub const DEV_KEY_VAR: &str = "HIREZ_DEV_KEY";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AuthError {
+    Io(String),
+    Parse(String),
+    MissingField(String),
+    InvalidDevId(String),
+    // the key itself is left out so it never ends up in logs
+    InvalidDevKey,
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuthError::Io(msg) => write!(f, "Error reading credentials: {}", msg),
+            AuthError::Parse(msg) => write!(f, "Error parsing credentials: {}", msg),
+            AuthError::MissingField(field) => write!(f, "Credentials are missing {}", field),
+            AuthError::InvalidDevId(dev_id) => {
+                write!(f, "Dev id must be numeric, found: {}", dev_id)
+            }
+            AuthError::InvalidDevKey => write!(f, "Dev key must be 32 hexadecimal characters"),
+        }
+    }
+}
+
+impl Error for AuthError {}
+
+impl From<AuthError> for String {
+    fn from(error: AuthError) -> String {
+        error.to_string()
+    }
+}
+
+// config files may give the dev id as either a number or a string
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ConfigDevId {
+    Number(u64),
+    Text(String),
+}
+
+#[derive(Deserialize)]
+struct AuthConfig {
+    dev_id: Option<ConfigDevId>,
+    dev_key: Option<String>,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Auth {
+    pub dev_id: String,
+    pub dev_key: String,
+}
+
+impl fmt::Debug for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Auth")
+            .field("dev_id", &self.dev_id)
+            .field("dev_key", &"<redacted>")
+            .finish()
+    }
+}
+
+impl Auth {
+    /*
+     * Validates that the dev id is numeric and the dev key is 32 hex characters
+     */
+    pub fn new(dev_id: &str, dev_key: &str) -> Result<Auth, AuthError> {
+        let dev_id = dev_id.trim();
+        let dev_key = dev_key.trim();
+
+        if dev_id.is_empty() {
+            return Err(AuthError::MissingField(String::from("dev_id")));
+        } else if dev_key.is_empty() {
+            return Err(AuthError::MissingField(String::from("dev_key")));
+        } else if !dev_id.chars().all(|x| x.is_ascii_digit()) {
+            return Err(AuthError::InvalidDevId(String::from(dev_id)));
+        } else if dev_key.len() != 32 || !dev_key.chars().all(|x| x.is_ascii_hexdigit()) {
+            return Err(AuthError::InvalidDevKey);
+        }
+
+        Ok(Auth {
+            dev_id: String::from(dev_id),
+            dev_key: String::from(dev_key),
+        })
+    }
+
+    /*
+     * Reads the dev id and key from HIREZ_DEV_ID and HIREZ_DEV_KEY
+     */
+    pub fn from_env() -> Result<Auth, AuthError> {
+        let dev_id = env::var(DEV_ID_VAR).map_err(|_| AuthError::MissingField(String::from(DEV_ID_VAR)))?;
+        let dev_key =
+            env::var(DEV_KEY_VAR).map_err(|_| AuthError::MissingField(String::from(DEV_KEY_VAR)))?;
+        Auth::new(&dev_id, &dev_key)
+    }
+
+    /*
+     * Reads a file with the dev id on the first line and the dev key on the second
+     */
+    pub fn from_file(path: &str) -> Result<Auth, AuthError> {
+        Auth::from_lines(&read_to_string(path)?)
+    }
+
+    /*
+     * Reads a TOML or JSON file with dev_id and dev_key entries, chosen by file extension
+     */
+    pub fn from_config(path: &str) -> Result<Auth, AuthError> {
+        let contents = read_to_string(path)?;
+        match Path::new(path).extension().and_then(|x| x.to_str()) {
+            Some("json") => Auth::from_json(&contents),
+            Some("toml") => Auth::from_toml(&contents),
+            _ => Err(AuthError::Parse(format!(
+                "Unrecognized config file extension: {}",
+                path
+            ))),
+        }
+    }
+
+    /*
+     * Uses the environment when both variables are set, otherwise the file at path, which may
+     * be a TOML or JSON config or the two-line format
+     */
+    pub fn load(path: &str) -> Result<Auth, AuthError> {
+        if env::var(DEV_ID_VAR).is_ok() && env::var(DEV_KEY_VAR).is_ok() {
+            return Auth::from_env();
+        }
+
+        match Path::new(path).extension().and_then(|x| x.to_str()) {
+            Some("json") | Some("toml") => Auth::from_config(path),
+            _ => Auth::from_file(path),
+        }
+    }
+
+    fn from_lines(contents: &str) -> Result<Auth, AuthError> {
+        // trimming each line also strips the \r left by editors on Windows
+        let mut lines = contents.lines().map(|x| x.trim()).filter(|x| !x.is_empty());
+        let dev_id = lines
+            .next()
+            .ok_or_else(|| AuthError::MissingField(String::from("dev_id")))?;
+        let dev_key = lines
+            .next()
+            .ok_or_else(|| AuthError::MissingField(String::from("dev_key")))?;
+        Auth::new(dev_id, dev_key)
+    }
+
+    fn from_json(contents: &str) -> Result<Auth, AuthError> {
+        let config: AuthConfig =
+            serde_json::from_str(contents).map_err(|msg| AuthError::Parse(msg.to_string()))?;
+        Auth::from_auth_config(config)
+    }
+
+    fn from_toml(contents: &str) -> Result<Auth, AuthError> {
+        let config: AuthConfig =
+            toml::from_str(contents).map_err(|msg| AuthError::Parse(msg.to_string()))?;
+        Auth::from_auth_config(config)
+    }
+
+    fn from_auth_config(config: AuthConfig) -> Result<Auth, AuthError> {
+        let dev_id = match config.dev_id {
+            Some(ConfigDevId::Number(dev_id)) => dev_id.to_string(),
+            Some(ConfigDevId::Text(dev_id)) => dev_id,
+            None => return Err(AuthError::MissingField(String::from("dev_id"))),
+        };
+        let dev_key = config
+            .dev_key
+            .ok_or_else(|| AuthError::MissingField(String::from("dev_key")))?;
+        Auth::new(&dev_id, &dev_key)
+    }
+}
+
+fn read_to_string(path: &str) -> Result<String, AuthError> {
+    let mut file = File::open(path).map_err(|msg| AuthError::Io(format!("{}: {}", path, msg)))?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)
+        .map_err(|msg| AuthError::Io(format!("{}: {}", path, msg)))?;
+    Ok(contents)
+}
+
+#[cfg(test)]
+test_suite! {
+    name test_auth;
+    use super::*;
+
+    const DEV_KEY: &str = "0123456789ABCDEF0123456789abcdef";
+
+    test two_line_format_strips_carriage_returns() {
+        let auth = Auth::from_lines(&format!("1234\r\n{}\r\n", DEV_KEY)).unwrap();
+        assert_eq!(auth.dev_id, "1234");
+        assert_eq!(auth.dev_key, DEV_KEY);
+    }
+
+    test one_line_file_is_an_error() {
+        assert_eq!(Auth::from_lines("1234\n"), Err(AuthError::MissingField(String::from("dev_key"))));
+    }
+
+    test invalid_credentials_are_rejected() {
+        assert_eq!(Auth::new("12a4", DEV_KEY), Err(AuthError::InvalidDevId(String::from("12a4"))));
+        assert_eq!(Auth::new("1234", "0123"), Err(AuthError::InvalidDevKey));
+        assert_eq!(
+            Auth::new("1234", "0123456789ABCDEF0123456789abcdeg"),
+            Err(AuthError::InvalidDevKey)
+        );
+    }
+
+    test config_formats() {
+        let from_json = Auth::from_json(&format!("{{ \"dev_id\": 1234, \"dev_key\": \"{}\" }}", DEV_KEY)).unwrap();
+        let from_toml = Auth::from_toml(&format!("dev_id = \"1234\"\ndev_key = \"{}\"\n", DEV_KEY)).unwrap();
+        assert_eq!(from_json, from_toml);
+        assert_eq!(from_json.dev_id, "1234");
+
+        assert_eq!(
+            Auth::from_toml("dev_id = 1234\n"),
+            Err(AuthError::MissingField(String::from("dev_key")))
+        );
+        assert!(Auth::from_json("dev_id = 1234").is_err());
+    }
+
+    test missing_file_is_an_error() {
+        match Auth::from_file("does-not-exist.txt") {
+            Err(AuthError::Io(_)) => {}
+            other => panic!("expected an io error, got {:?}", other),
+        }
+    }
+
+    test debug_hides_dev_key() {
+        let auth = Auth::new("1234", DEV_KEY).unwrap();
+        assert!(!format!("{:?}", auth).contains(DEV_KEY));
+    }
+}
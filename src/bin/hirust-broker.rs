@@ -0,0 +1,62 @@
+/*
+ * Owns the sessions for one dev id and leases them to HiRust clients over a Unix socket
+ *
+ * usage: hirust-broker <path to credentials> [socket path] [session store path]
+ */
+#[cfg(unix)]
+fn main() {
+    use std::env;
+    use std::path::PathBuf;
+    use std::process;
+
+    use hirust::broker::{default_socket_path, SessionBroker};
+    use hirust::hi_rez_constants::UrlConstants;
+    use hirust::session_manager::{Auth, SessionManager};
+    use hirust::session_store::FileSessionStore;
+
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        println!("usage: hirust-broker <path to credentials> [socket path] [session store path]");
+        process::exit(2);
+    }
+
+    let socket_path = match args.get(2) {
+        Some(socket_path) => PathBuf::from(socket_path),
+        None => match default_socket_path() {
+            Ok(socket_path) => socket_path,
+            Err(msg) => {
+                println!("{}", msg);
+                process::exit(1);
+            }
+        },
+    };
+    let store_path = args.get(3).map(String::as_str).unwrap_or("sessions.json");
+
+    let auth = match Auth::load(&args[1]) {
+        Ok(auth) => auth,
+        Err(msg) => {
+            println!("{}", msg);
+            process::exit(1);
+        }
+    };
+    let session_store = Box::new(FileSessionStore::new(store_path));
+    let session_manager = match SessionManager::with_store(auth, UrlConstants::UrlBase, session_store) {
+        Ok(session_manager) => session_manager,
+        Err(msg) => {
+            println!("{}", msg);
+            process::exit(1);
+        }
+    };
+
+    println!("hirust-broker listening on {}", socket_path.display());
+    if let Err(msg) = SessionBroker::new(session_manager).serve(&socket_path) {
+        println!("{}", msg);
+        process::exit(1);
+    }
+}
+
+#[cfg(not(unix))]
+fn main() {
+    println!("hirust-broker requires Unix domain sockets");
+    std::process::exit(1);
+}
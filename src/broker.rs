@@ -0,0 +1,582 @@
+use serde::{Deserialize, Serialize};
+use serde_json;
+use std::collections::HashMap;
+use std::fs;
+use std::env;
+use std::io::prelude::*;
+use std::io::{BufReader, ErrorKind};
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::hi_rez_constants::ReturnDataType;
+use crate::session_manager::{DailyCounters, SessionManager, SessionProvider};
+
+#[cfg(test)]
+use galvanic_test::test_suite;
+
+const SOCKET_NAME: &str = "hirust-broker.sock";
+
+/*
+ * Where the broker listens unless told otherwise, somewhere only the current user can reach
+ *
+ * That is $XDG_RUNTIME_DIR when set, or else a directory in the temp directory created with
+ * mode 0700, since anyone who can connect can spend the broker's quota
+ */
+pub fn default_socket_path() -> Result<PathBuf, String> {
+    if let Some(dir) = env::var_os("XDG_RUNTIME_DIR") {
+        return Ok(PathBuf::from(dir).join(SOCKET_NAME));
+    }
+
+    let user = env::var("USER").unwrap_or_else(|_| String::from("default"));
+    let dir = env::temp_dir().join(format!("hirust-broker-{}", user));
+    match fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Ok(()) => {}
+        Err(ref msg) if msg.kind() == ErrorKind::AlreadyExists => {}
+        Err(msg) => return Err(format!("Error creating {}: {}", dir.display(), msg)),
+    }
+
+    // another user may have made it first, in which case it can't be trusted
+    let mode = fs::metadata(&dir)
+        .map_err(|msg| format!("Error reading {}: {}", dir.display(), msg))?
+        .permissions()
+        .mode();
+    if mode & 0o077 != 0 {
+        return Err(format!(
+            "{} is accessible to other users, remove it or choose a socket path",
+            dir.display()
+        ));
+    }
+    Ok(dir.join(SOCKET_NAME))
+}
+
+/*
+ * Removes a socket left behind by a broker that is no longer running, refusing to touch a
+ * live broker's socket or anything that isn't a socket
+ */
+fn remove_stale_socket(socket_path: &Path) -> Result<(), String> {
+    let metadata = match fs::symlink_metadata(socket_path) {
+        Ok(metadata) => metadata,
+        Err(ref msg) if msg.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(msg) => return Err(format!("Error reading {}: {}", socket_path.display(), msg)),
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(format!("{} exists and is not a socket", socket_path.display()));
+    }
+    if UnixStream::connect(socket_path).is_ok() {
+        return Err(format!("A broker is already listening on {}", socket_path.display()));
+    }
+
+    fs::remove_file(socket_path)
+        .map_err(|msg| format!("Error removing stale socket {}: {}", socket_path.display(), msg))
+}
+
+/*
+ * Binds the socket inside a directory only the current user can enter, restricts it, and only
+ * then moves it to socket_path, so it is never reachable by other users
+ */
+fn bind_private(socket_path: &Path) -> Result<UnixListener, String> {
+    let name = socket_path
+        .file_name()
+        .ok_or_else(|| format!("{} is not a file path", socket_path.display()))?;
+    let private_dir =
+        socket_path.with_file_name(format!(".{}.{}", name.to_string_lossy(), process::id()));
+    fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&private_dir)
+        .map_err(|msg| format!("Error creating {}: {}", private_dir.display(), msg))?;
+
+    let private_path = private_dir.join(name);
+    let result = UnixListener::bind(&private_path)
+        .map_err(|msg| format!("Error binding broker socket: {}", msg))
+        .and_then(|listener| {
+            fs::set_permissions(&private_path, fs::Permissions::from_mode(0o600))
+                .map_err(|msg| format!("Error restricting broker socket: {}", msg))?;
+            fs::rename(&private_path, socket_path)
+                .map_err(|msg| format!("Error moving broker socket to {}: {}", socket_path.display(), msg))?;
+            Ok(listener)
+        });
+    let _ = fs::remove_file(&private_path);
+    let _ = fs::remove_dir(&private_dir);
+    result
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type")]
+pub enum BrokerRequest {
+    // leasing a session spends one request from the daily budget
+    Acquire,
+    Release {
+        session_key: String,
+    },
+    Invalidate {
+        session_key: String,
+    },
+    Sign {
+        session_key: String,
+        method_name: String,
+        data_type: ReturnDataType,
+        method_specific: String,
+    },
+    Status,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type")]
+pub enum BrokerReply {
+    Session { session_key: String },
+    Url { url: String },
+    Status { counters: DailyCounters, leased: usize },
+    Released,
+    Error { message: String },
+}
+
+fn send<T: Serialize>(stream: &mut UnixStream, message: &T) -> Result<(), String> {
+    let mut line = serde_json::to_string(message)
+        .map_err(|msg| format!("Error serializing broker message: {}", msg))?;
+    line.push('\n');
+    stream
+        .write_all(line.as_bytes())
+        .map_err(|msg| format!("Error writing to broker socket: {}", msg))
+}
+
+// returns None once the other end has closed the connection
+fn receive<T>(reader: &mut BufReader<UnixStream>) -> Result<Option<T>, String>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let mut line = String::new();
+    let num_bytes = reader
+        .read_line(&mut line)
+        .map_err(|msg| format!("Error reading from broker socket: {}", msg))?;
+    if num_bytes == 0 {
+        return Ok(None);
+    }
+
+    serde_json::from_str(&line)
+        .map(Some)
+        .map_err(|msg| format!("Error deserializing broker message: {}", msg))
+}
+
+/*
+ * Owns the SessionManager for a dev id and leases its sessions to client processes
+ */
+pub struct SessionBroker {
+    session_manager: Arc<SessionManager>,
+    leased: Arc<Mutex<usize>>,
+}
+
+impl SessionBroker {
+    pub fn new(session_manager: SessionManager) -> SessionBroker {
+        SessionBroker {
+            session_manager: Arc::new(session_manager),
+            leased: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    /*
+     * Accepts clients until the listener fails, serving each on its own thread
+     *
+     * The socket is only usable by the current user. Fails if another broker is already
+     * listening on socket_path
+     */
+    pub fn serve<P: AsRef<Path>>(&self, socket_path: P) -> Result<(), String> {
+        let socket_path = socket_path.as_ref();
+        remove_stale_socket(socket_path)?;
+        let listener = bind_private(socket_path)?;
+
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(msg) => {
+                    println!("Error accepting broker client: {}", msg);
+                    continue;
+                }
+            };
+
+            let session_manager = Arc::clone(&self.session_manager);
+            let leased = Arc::clone(&self.leased);
+            thread::spawn(move || {
+                if let Err(msg) = SessionBroker::handle_client(&session_manager, &leased, stream) {
+                    println!("{}", msg);
+                }
+
+                if let Err(msg) = session_manager.store() {
+                    println!("{}", msg);
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    fn handle_client(
+        session_manager: &SessionManager,
+        leased: &Mutex<usize>,
+        stream: UnixStream,
+    ) -> Result<(), String> {
+        let mut writer = stream
+            .try_clone()
+            .map_err(|msg| format!("Error cloning broker socket: {}", msg))?;
+        let mut reader = BufReader::new(stream);
+        let mut client_leases: Vec<String> = Vec::new();
+
+        let result = loop {
+            let request: BrokerRequest = match receive(&mut reader) {
+                Ok(Some(request)) => request,
+                Ok(None) => break Ok(()),
+                Err(msg) => break Err(msg),
+            };
+
+            let reply =
+                SessionBroker::handle_request(session_manager, leased, &mut client_leases, request);
+            if let Err(msg) = send(&mut writer, &reply) {
+                break Err(msg);
+            }
+        };
+
+        // the client went away without returning its sessions
+        for session_key in client_leases {
+            session_manager.replace_session(session_key);
+            *leased.lock().unwrap() -= 1;
+        }
+
+        result
+    }
+
+    pub fn handle_request(
+        session_manager: &SessionManager,
+        leased: &Mutex<usize>,
+        client_leases: &mut Vec<String>,
+        request: BrokerRequest,
+    ) -> BrokerReply {
+        let not_leased = BrokerReply::Error {
+            message: String::from("Session not leased by this client"),
+        };
+
+        match request {
+            BrokerRequest::Acquire => match session_manager.get_session_key_concurrent() {
+                Ok(session_key) => {
+                    client_leases.push(session_key.clone());
+                    *leased.lock().unwrap() += 1;
+                    BrokerReply::Session { session_key }
+                }
+                Err(message) => BrokerReply::Error { message },
+            },
+            BrokerRequest::Release { session_key } | BrokerRequest::Invalidate { session_key }
+                if !client_leases.contains(&session_key) =>
+            {
+                not_leased
+            }
+            BrokerRequest::Release { session_key } => {
+                client_leases.retain(|x| *x != session_key);
+                *leased.lock().unwrap() -= 1;
+                session_manager.replace_session(session_key);
+                BrokerReply::Released
+            }
+            BrokerRequest::Invalidate { session_key } => {
+                client_leases.retain(|x| *x != session_key);
+                *leased.lock().unwrap() -= 1;
+                session_manager.remove_invalid_session(session_key);
+                BrokerReply::Released
+            }
+            BrokerRequest::Sign { session_key, .. } if !client_leases.contains(&session_key) => {
+                not_leased
+            }
+            BrokerRequest::Sign {
+                session_key,
+                method_name,
+                data_type,
+                method_specific,
+            } => match SessionProvider::url(
+                session_manager,
+                &session_key,
+                &method_name,
+                &data_type,
+                &method_specific,
+            ) {
+                Ok(url) => BrokerReply::Url { url },
+                Err(message) => BrokerReply::Error { message },
+            },
+            BrokerRequest::Status => BrokerReply::Status {
+                counters: session_manager.counters(),
+                leased: *leased.lock().unwrap(),
+            },
+        }
+    }
+}
+
+type Connection = BufReader<UnixStream>;
+
+/*
+ * A SessionProvider backed by a SessionBroker, so every client shares the broker's quota
+ */
+pub struct BrokeredSessionManager {
+    socket_path: PathBuf,
+    idle_connections: Mutex<Vec<Connection>>,
+    // each lease stays on the connection it was acquired over
+    leased_connections: Mutex<HashMap<String, Connection>>,
+}
+
+impl BrokeredSessionManager {
+    pub fn new<P: AsRef<Path>>(socket_path: P) -> BrokeredSessionManager {
+        BrokeredSessionManager {
+            socket_path: socket_path.as_ref().to_path_buf(),
+            idle_connections: Mutex::new(Vec::new()),
+            leased_connections: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn status(&self) -> Result<(DailyCounters, usize), String> {
+        let mut connection = self.connection()?;
+        let reply = BrokeredSessionManager::call(&mut connection, &BrokerRequest::Status);
+        self.idle_connections.lock().unwrap().push(connection);
+
+        match reply? {
+            BrokerReply::Status { counters, leased } => Ok((counters, leased)),
+            reply => BrokeredSessionManager::unexpected(reply),
+        }
+    }
+
+    fn connection(&self) -> Result<Connection, String> {
+        if let Some(connection) = self.idle_connections.lock().unwrap().pop() {
+            return Ok(connection);
+        }
+
+        UnixStream::connect(&self.socket_path)
+            .map(BufReader::new)
+            .map_err(|msg| format!("Error connecting to broker: {}", msg))
+    }
+
+    fn call(connection: &mut Connection, request: &BrokerRequest) -> Result<BrokerReply, String> {
+        send(connection.get_mut(), request)?;
+        match receive(connection)? {
+            Some(reply) => Ok(reply),
+            None => Err(String::from("Broker closed the connection")),
+        }
+    }
+
+    fn unexpected<T>(reply: BrokerReply) -> Result<T, String> {
+        match reply {
+            BrokerReply::Error { message } => Err(message),
+            reply => Err(format!("Unexpected broker reply: {:?}", reply)),
+        }
+    }
+
+    fn give_back(&self, session_key: String, request: BrokerRequest) {
+        let connection = self.leased_connections.lock().unwrap().remove(&session_key);
+        let mut connection = match connection {
+            Some(connection) => connection,
+            None => {
+                println!("Session not leased from broker: {}", session_key);
+                return;
+            }
+        };
+
+        match BrokeredSessionManager::call(&mut connection, &request) {
+            Ok(BrokerReply::Released) => self.idle_connections.lock().unwrap().push(connection),
+            Ok(reply) => {
+                if let Err(msg) = BrokeredSessionManager::unexpected::<()>(reply) {
+                    println!("{}", msg);
+                }
+                self.idle_connections.lock().unwrap().push(connection);
+            }
+            // drop the connection, which makes the broker reclaim the session
+            Err(msg) => println!("{}", msg),
+        }
+    }
+}
+
+impl SessionProvider for BrokeredSessionManager {
+    fn get_session_key_concurrent(&self) -> Result<String, String> {
+        let mut connection = self.connection()?;
+        match BrokeredSessionManager::call(&mut connection, &BrokerRequest::Acquire)? {
+            BrokerReply::Session { session_key } => {
+                self.leased_connections
+                    .lock()
+                    .unwrap()
+                    .insert(session_key.clone(), connection);
+                Ok(session_key)
+            }
+            reply => {
+                self.idle_connections.lock().unwrap().push(connection);
+                BrokeredSessionManager::unexpected(reply)
+            }
+        }
+    }
+
+    fn replace_session(&self, session_key: String) {
+        let request = BrokerRequest::Release {
+            session_key: session_key.clone(),
+        };
+        self.give_back(session_key, request);
+    }
+
+    fn remove_invalid_session(&self, session_key: String) {
+        let request = BrokerRequest::Invalidate {
+            session_key: session_key.clone(),
+        };
+        self.give_back(session_key, request);
+    }
+
+    fn url(
+        &self,
+        session_key: &str,
+        method_name: &str,
+        data_type: &ReturnDataType,
+        method_specific: &str,
+    ) -> Result<String, String> {
+        let connection = self.leased_connections.lock().unwrap().remove(session_key);
+        let mut connection = match connection {
+            Some(connection) => connection,
+            None => return Err(format!("Session not leased from broker: {}", session_key)),
+        };
+
+        let request = BrokerRequest::Sign {
+            session_key: session_key.to_string(),
+            method_name: method_name.to_string(),
+            data_type: data_type.clone(),
+            method_specific: method_specific.to_string(),
+        };
+        let reply = BrokeredSessionManager::call(&mut connection, &request);
+        self.leased_connections
+            .lock()
+            .unwrap()
+            .insert(session_key.to_string(), connection);
+
+        match reply? {
+            BrokerReply::Url { url } => Ok(url),
+            reply => BrokeredSessionManager::unexpected(reply),
+        }
+    }
+}
+
+#[cfg(test)]
+test_suite! {
+    name test_broker;
+    use super::*;
+    use crate::reqwest_wrapper::MockWrapper;
+    use std::env;
+    use std::process;
+    use std::time::Duration;
+
+    fn temp_socket(name: &str) -> PathBuf {
+        let mut path = env::temp_dir();
+        path.push(format!("hirust-{}-{}", process::id(), name));
+        path
+    }
+
+    fixture broker(name: &'static str) -> BrokeredSessionManager {
+        setup(&mut self) {
+            let mut reqwest = MockWrapper::new();
+            reqwest.expect_get_to_text().return_const(Ok(String::from(
+                "{ \"ret_msg\": \"Approved\", \"session_id\": \"1234567890\", \"timestamp\": null }",
+            )));
+            let broker = SessionBroker::new(SessionManager::mock(reqwest));
+            let socket_path = temp_socket(self.name);
+            let _ = fs::remove_file(&socket_path);
+
+            let serve_path = socket_path.clone();
+            thread::spawn(move || broker.serve(serve_path).unwrap());
+            while !socket_path.exists() {
+                thread::sleep(Duration::from_millis(10));
+            }
+
+            BrokeredSessionManager::new(socket_path)
+        }
+        tear_down(&self) {
+            let _ = fs::remove_file(temp_socket(self.name));
+        }
+    }
+
+    test acquire_sign_release(broker("acquire.sock")) {
+        let client = &broker.val;
+
+        let session_key = client.get_session_key_concurrent().unwrap();
+        assert_eq!(session_key, "1234567890");
+        assert_eq!(client.status().unwrap().1, 1);
+
+        let url = client.url(&session_key, "getgods", &ReturnDataType::Json, "/1").unwrap();
+        assert!(url.starts_with("https://api.smitegame.com/smiteapi.svc/getgodsjson/dummy/"));
+        assert!(url.contains("/1234567890/"));
+        assert!(url.ends_with("/1"));
+
+        client.replace_session(session_key.clone());
+        let (counters, leased) = client.status().unwrap();
+        assert_eq!(leased, 0);
+        assert_eq!(counters.num_requests, 1);
+        assert!(client.url(&session_key, "getgods", &ReturnDataType::Json, "/1").is_err());
+    }
+
+    test disconnect_returns_leases(broker("disconnect.sock")) {
+        let client = &broker.val;
+
+        let other_client = BrokeredSessionManager::new(temp_socket("disconnect.sock"));
+        other_client.get_session_key_concurrent().unwrap();
+        assert_eq!(client.status().unwrap().1, 1);
+        drop(other_client);
+
+        // the broker notices the closed connection asynchronously
+        let mut leased = 1;
+        for _ in 0..100 {
+            leased = client.status().unwrap().1;
+            if leased == 0 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(leased, 0);
+    }
+
+    test serve_leaves_live_brokers_alone(broker("live.sock")) {
+        let socket_path = temp_socket("live.sock");
+        // once it answers, the broker has finished setting up its socket
+        assert!(broker.val.status().is_ok());
+        let mode = fs::metadata(&socket_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // the directory it was bound in is gone
+        let private_dir = socket_path.with_file_name(format!(".hirust-{}-live.sock.{}", process::id(), process::id()));
+        assert!(!private_dir.exists());
+
+        let second = SessionBroker::new(SessionManager::mock(MockWrapper::new()));
+        assert!(second.serve(&socket_path).unwrap_err().contains("already listening"));
+        assert!(broker.val.status().is_ok());
+    }
+
+    test only_stale_sockets_are_removed() {
+        let not_socket = temp_socket("not-a-socket");
+        fs::write(&not_socket, "keep").unwrap();
+        assert!(remove_stale_socket(&not_socket).unwrap_err().contains("is not a socket"));
+        assert!(not_socket.exists());
+        fs::remove_file(&not_socket).unwrap();
+
+        let stale = temp_socket("stale.sock");
+        let _ = fs::remove_file(&stale);
+        drop(UnixListener::bind(&stale).unwrap());
+        remove_stale_socket(&stale).unwrap();
+        assert!(!stale.exists());
+    }
+
+    test sign_requires_lease() {
+        let reqwest = MockWrapper::new();
+        let session_manager = SessionManager::mock(reqwest);
+        let leased = Mutex::new(0);
+        let mut client_leases = Vec::new();
+
+        let reply = SessionBroker::handle_request(
+            &session_manager,
+            &leased,
+            &mut client_leases,
+            BrokerRequest::Sign {
+                session_key: String::from("someone-elses"),
+                method_name: String::from("getgods"),
+                data_type: ReturnDataType::Json,
+                method_specific: String::from("/1"),
+            },
+        );
+        assert_eq!(reply, BrokerReply::Error { message: String::from("Session not leased by this client") });
+    }
+}
@@ -0,0 +1,651 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::crawler::{CrawlResult, CrawledMatchId, MatchIdCrawler};
+use crate::endpoints::{GetMatchDetailsBatch, GetMatchIdsByQueueRequest, TimeWindow};
+use crate::hi_rez_constants::DataConstants;
+use crate::models::PlayerMatchDetails;
+use crate::request_maker::{RequestMaker, VALID_RETURN_MESSAGES};
+use crate::session_store::write_atomically;
+use crate::sink::Sink;
+
+#[cfg(test)]
+use galvanic_test::test_suite;
+
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+// requests made between saving checkpoints
+const DEFAULT_CHUNK_SIZE: usize = 45;
+
+/*
+ * Errors that mean no more requests can be made until the next UTC day
+ */
+pub fn is_daily_limit(msg: &str) -> bool {
+    msg.contains("per day reached")
+        || msg.starts_with("No credentials available")
+        || msg.to_lowercase().contains("daily request limit")
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CheckpointWindow {
+    pub queue_id: DataConstants,
+    pub date: NaiveDate,
+    pub window: TimeWindow,
+}
+
+impl CheckpointWindow {
+    fn from_request(request: &GetMatchIdsByQueueRequest) -> CheckpointWindow {
+        CheckpointWindow {
+            queue_id: request.queue_id,
+            date: request.date,
+            window: request.window,
+        }
+    }
+
+    fn to_request(&self) -> GetMatchIdsByQueueRequest {
+        GetMatchIdsByQueueRequest {
+            queue_id: self.queue_id,
+            date: self.date,
+            window: self.window,
+        }
+    }
+}
+
+// a match that was still being played when its window was crawled
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ActiveMatch {
+    pub match_id: String,
+    pub window: CheckpointWindow,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct FailedWindow {
+    pub window: CheckpointWindow,
+    pub error: String,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct FailedBatch {
+    pub match_ids: Vec<String>,
+    pub error: String,
+}
+
+/*
+ * Everything needed to resume a bulk pull
+ */
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Checkpoint {
+    pub version: u32,
+    pub queues: Vec<DataConstants>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub pending_windows: Vec<CheckpointWindow>,
+    pub windows_crawled: usize,
+    pub failed_windows: Vec<FailedWindow>,
+    // every match id found so far, so none are fetched twice
+    pub discovered: Vec<String>,
+    pub pending_match_ids: Vec<String>,
+    // waiting to be handed to a PendingMatchTracker
+    #[serde(default)]
+    pub active_matches: Vec<ActiveMatch>,
+    // ten minute windows that still reached the truncation limit, so may be missing matches
+    #[serde(default)]
+    pub truncated_windows: Vec<CheckpointWindow>,
+    pub batches_fetched: usize,
+    pub failed_batches: Vec<FailedBatch>,
+}
+
+impl Checkpoint {
+    pub fn new(queues: Vec<DataConstants>, start: DateTime<Utc>, end: DateTime<Utc>) -> Checkpoint {
+        let pending_windows = queues
+            .iter()
+            .flat_map(|&queue_id| GetMatchIdsByQueueRequest::for_range(queue_id, start, end))
+            .map(|x| CheckpointWindow::from_request(&x))
+            .collect();
+        Checkpoint {
+            version: CHECKPOINT_VERSION,
+            queues,
+            start,
+            end,
+            pending_windows,
+            windows_crawled: 0,
+            failed_windows: Vec::new(),
+            discovered: Vec::new(),
+            pending_match_ids: Vec::new(),
+            active_matches: Vec::new(),
+            truncated_windows: Vec::new(),
+            batches_fetched: 0,
+            failed_batches: Vec::new(),
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.pending_windows.is_empty() && self.pending_match_ids.is_empty()
+    }
+
+    pub fn load(path: &Path) -> Result<Option<Checkpoint>, String> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(msg) => return Err(format!("Error opening checkpoint: {}", msg)),
+        };
+        let mut all_text = String::new();
+        file.read_to_string(&mut all_text)
+            .map_err(|msg| format!("Error reading checkpoint: {}", msg))?;
+
+        let checkpoint: Checkpoint = serde_json::from_str(&all_text)
+            .map_err(|msg| format!("Error deserializing checkpoint: {}", msg))?;
+        if checkpoint.version != CHECKPOINT_VERSION {
+            return Err(format!("Unsupported checkpoint version: {}", checkpoint.version));
+        }
+        Ok(Some(checkpoint))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let checkpoint_str = serde_json::to_string_pretty(self)
+            .map_err(|msg| format!("Error serializing checkpoint: {}", msg))?;
+        write_atomically(path, checkpoint_str.as_bytes())
+            .map_err(|msg| format!("Error writing checkpoint: {}", msg))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JobStatus {
+    Complete,
+    // a daily limit was reached, run again on the next UTC day to continue
+    Paused(String),
+}
+
+/*
+ * Crawls the match ids of some queues over a range of dates and fetches their details,
+ * saving a checkpoint as it goes so an interrupted job picks up where it left off
+ */
+pub struct BulkJob<'a> {
+    request_maker: &'a RequestMaker,
+    checkpoint_path: PathBuf,
+    checkpoint: Checkpoint,
+    chunk_size: usize,
+    split_empty: bool,
+    truncation_limit: Option<usize>,
+}
+
+impl<'a> BulkJob<'a> {
+    /*
+     * Resumes the job checkpointed at checkpoint_path, or starts a new one if there isn't one
+     */
+    pub fn open<P: AsRef<Path>>(
+        request_maker: &'a RequestMaker,
+        checkpoint_path: P,
+        queues: Vec<DataConstants>,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<BulkJob<'a>, String> {
+        let checkpoint_path = checkpoint_path.as_ref().to_path_buf();
+        let checkpoint = match Checkpoint::load(&checkpoint_path)? {
+            Some(checkpoint) => {
+                if checkpoint.queues != queues || checkpoint.start != start || checkpoint.end != end {
+                    return Err(format!(
+                        "Checkpoint {} belongs to a different job",
+                        checkpoint_path.display()
+                    ));
+                }
+                checkpoint
+            }
+            None => Checkpoint::new(queues, start, end),
+        };
+
+        Ok(BulkJob {
+            request_maker,
+            checkpoint_path,
+            checkpoint,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            // HiRez returns nothing for a window with too many matches
+            split_empty: true,
+            truncation_limit: None,
+        })
+    }
+
+    pub fn chunk_size(mut self, chunk_size: usize) -> BulkJob<'a> {
+        self.chunk_size = cmp::max(chunk_size, 1);
+        self
+    }
+
+    // whether empty windows are crawled again as smaller windows, true by default
+    pub fn split_empty(mut self, split_empty: bool) -> BulkJob<'a> {
+        self.split_empty = split_empty;
+        self
+    }
+
+    // windows with at least this many replies are crawled again as smaller windows
+    pub fn truncation_limit(mut self, limit: usize) -> BulkJob<'a> {
+        self.truncation_limit = Some(limit);
+        self
+    }
+
+    pub fn checkpoint(&self) -> &Checkpoint {
+        &self.checkpoint
+    }
+
+    // queues the failed windows and batches to be tried again on the next run
+    pub fn retry_failed(&mut self) -> Result<(), String> {
+        let checkpoint = &mut self.checkpoint;
+        for failed in checkpoint.failed_windows.drain(..) {
+            checkpoint.pending_windows.push(failed.window);
+        }
+        for failed in checkpoint.failed_batches.drain(..) {
+            checkpoint.pending_match_ids.extend(failed.match_ids);
+        }
+        self.checkpoint.save(&self.checkpoint_path)
+    }
+
+    /*
+     * Lists the matches that were still active when crawled so they can be given to a
+     * PendingMatchTracker
+     *
+     * They stay in the checkpoint until add_finished or drop_expired is called with them, so a
+     * crash in between doesn't lose them
+     */
+    pub fn active_matches(&self) -> Vec<CrawledMatchId> {
+        self.checkpoint
+            .active_matches
+            .iter()
+            .map(|x| CrawledMatchId {
+                match_id: x.match_id.clone(),
+                queue_id: x.window.queue_id,
+                date: x.window.date,
+                window: x.window.window,
+            })
+            .collect()
+    }
+
+    // queues matches a PendingMatchTracker found finished, skipping any already discovered
+    pub fn add_finished(&mut self, finished: Vec<CrawledMatchId>) -> Result<(), String> {
+        let checkpoint = &mut self.checkpoint;
+        let mut seen: HashSet<String> = checkpoint.discovered.iter().cloned().collect();
+        let finished_ids: HashSet<String> = finished.iter().map(|x| x.match_id.clone()).collect();
+        checkpoint.active_matches.retain(|x| !finished_ids.contains(&x.match_id));
+        for crawled in finished {
+            if seen.insert(crawled.match_id.clone()) {
+                checkpoint.discovered.push(crawled.match_id.clone());
+                checkpoint.pending_match_ids.push(crawled.match_id);
+            }
+        }
+        checkpoint.save(&self.checkpoint_path)
+    }
+
+    // forgets active matches a PendingMatchTracker gave up on
+    pub fn drop_expired(&mut self, expired: Vec<CrawledMatchId>) -> Result<(), String> {
+        let expired_ids: HashSet<String> = expired.into_iter().map(|x| x.match_id).collect();
+        self.checkpoint.active_matches.retain(|x| !expired_ids.contains(&x.match_id));
+        self.checkpoint.save(&self.checkpoint_path)
+    }
+
+    /*
+     * Runs until the job is done or a daily limit is reached, passing the details of each batch
+     * to on_details as they arrive
+     *
+     * A batch is only marked as fetched once on_details returns Ok
+     */
+    pub fn run<F>(&mut self, mut on_details: F) -> Result<JobStatus, String>
+    where
+        F: FnMut(Vec<PlayerMatchDetails>) -> Result<(), String>,
+    {
+        while !self.checkpoint.pending_windows.is_empty() {
+            if let Some(msg) = self.crawl_chunk()? {
+                return Ok(JobStatus::Paused(msg));
+            }
+        }
+        while !self.checkpoint.pending_match_ids.is_empty() {
+            if let Some(msg) = self.fetch_chunk(&mut on_details)? {
+                return Ok(JobStatus::Paused(msg));
+            }
+        }
+        Ok(JobStatus::Complete)
+    }
+
+    /*
+     * Runs the job, writing each batch of details to sink and flushing it before returning
+     */
+    pub fn run_to_sink(&mut self, sink: &mut dyn Sink) -> Result<JobStatus, String> {
+        let status = self.run(|details| sink.write(&details));
+        sink.flush()?;
+        status
+    }
+
+    // returns the daily limit message if one was reached
+    fn crawl_chunk(&mut self) -> Result<Option<String>, String> {
+        let limit = cmp::min(self.chunk_size, self.checkpoint.pending_windows.len());
+        let requests: Vec<GetMatchIdsByQueueRequest> = self
+            .checkpoint
+            .pending_windows
+            .drain(..limit)
+            .map(|x| x.to_request())
+            .collect();
+        let num_requests = requests.len();
+
+        let mut seen: HashSet<String> = self.checkpoint.discovered.iter().cloned().collect();
+        seen.extend(self.checkpoint.active_matches.iter().map(|x| x.match_id.clone()));
+        let mut result = CrawlResult::default();
+        let mut crawler = MatchIdCrawler::new(self.request_maker).split_empty(self.split_empty);
+        if let Some(limit) = self.truncation_limit {
+            crawler = crawler.truncation_limit(limit);
+        }
+        let split = crawler.crawl_windows(requests, &mut seen, &mut result);
+
+        let checkpoint = &mut self.checkpoint;
+        let mut limit_message = None;
+        let mut requeued = Vec::new();
+        for (request, msg) in result.errors {
+            let window = CheckpointWindow::from_request(&request);
+            if is_daily_limit(&msg) {
+                requeued.push(window);
+                limit_message = Some(msg);
+            } else {
+                checkpoint.failed_windows.push(FailedWindow { window, error: msg });
+            }
+        }
+        checkpoint.windows_crawled += num_requests - requeued.len();
+        requeued.append(&mut checkpoint.pending_windows);
+        checkpoint.pending_windows = requeued;
+        checkpoint
+            .pending_windows
+            .extend(split.iter().map(CheckpointWindow::from_request));
+
+        for crawled in result.match_ids {
+            checkpoint.discovered.push(crawled.match_id.clone());
+            checkpoint.pending_match_ids.push(crawled.match_id);
+        }
+        checkpoint
+            .truncated_windows
+            .extend(result.truncated.iter().map(CheckpointWindow::from_request));
+        for crawled in result.active_match_ids {
+            checkpoint.active_matches.push(ActiveMatch {
+                window: CheckpointWindow {
+                    queue_id: crawled.queue_id,
+                    date: crawled.date,
+                    window: crawled.window,
+                },
+                match_id: crawled.match_id,
+            });
+        }
+
+        checkpoint.save(&self.checkpoint_path)?;
+        Ok(limit_message)
+    }
+
+    fn fetch_chunk<F>(&mut self, on_details: &mut F) -> Result<Option<String>, String>
+    where
+        F: FnMut(Vec<PlayerMatchDetails>) -> Result<(), String>,
+    {
+        let checkpoint = &mut self.checkpoint;
+        let limit = cmp::min(self.chunk_size * 10, checkpoint.pending_match_ids.len());
+        let match_ids: Vec<String> = checkpoint.pending_match_ids.drain(..limit).collect();
+        let batches: Vec<GetMatchDetailsBatch> = match_ids
+            .chunks(10)
+            .map(|x| GetMatchDetailsBatch {
+                match_ids: x.to_vec(),
+            })
+            .collect();
+
+        let replies = self.request_maker.execute(batches.clone());
+
+        let mut limit_message = None;
+        let mut callback_error = None;
+        let mut requeued: Vec<String> = Vec::new();
+        for (batch, reply) in batches.into_iter().zip(replies) {
+            // once on_details fails the rest are left for the next run
+            if callback_error.is_some() {
+                requeued.extend(batch.match_ids);
+                continue;
+            }
+
+            let reply = reply.and_then(|details| {
+                match details.first().and_then(|x| x.ret_msg.clone()) {
+                    Some(msg) if !VALID_RETURN_MESSAGES.contains(&msg.as_str()) => {
+                        Err(format!("GetMatchDetails Request Error: {}", msg))
+                    }
+                    _ => Ok(details),
+                }
+            });
+            match reply {
+                Ok(details) => match on_details(details) {
+                    Ok(()) => checkpoint.batches_fetched += 1,
+                    Err(msg) => {
+                        requeued.extend(batch.match_ids);
+                        callback_error = Some(msg);
+                    }
+                },
+                Err(msg) => {
+                    if is_daily_limit(&msg) {
+                        requeued.extend(batch.match_ids);
+                        limit_message = Some(msg);
+                    } else {
+                        checkpoint.failed_batches.push(FailedBatch {
+                            match_ids: batch.match_ids,
+                            error: msg,
+                        });
+                    }
+                }
+            }
+        }
+        requeued.append(&mut checkpoint.pending_match_ids);
+        checkpoint.pending_match_ids = requeued;
+
+        checkpoint.save(&self.checkpoint_path)?;
+        match callback_error {
+            Some(msg) => Err(msg),
+            None => Ok(limit_message),
+        }
+    }
+}
+
+#[cfg(test)]
+test_suite! {
+    name test_bulk;
+    use super::*;
+    use chrono::TimeZone;
+    use std::env;
+    use std::fs;
+    use std::sync::{Arc, Mutex};
+    use crate::reqwest_wrapper::MockWrapper as ReqwestWrapper;
+
+    fixture temp_checkpoint(name: &'static str) -> PathBuf {
+        params {
+            vec!["bulk-checkpoint.json"].into_iter()
+        }
+        setup(&mut self) {
+            let path = env::temp_dir().join(format!("hirust-{}-{}", std::process::id(), self.name));
+            let _ = fs::remove_file(&path);
+            path
+        }
+        tear_down(&self) {
+            let _ = fs::remove_file(env::temp_dir().join(format!("hirust-{}-{}", std::process::id(), self.name)));
+        }
+    }
+
+    fn match_ids_reply(match_ids: &[&str]) -> String {
+        let replies: Vec<String> = match_ids
+            .iter()
+            .map(|x| format!("{{ \"ret_msg\": null, \"Active_Flag\": \"n\", \"Match\": \"{}\" }}", x))
+            .collect();
+        format!("[{}]", replies.join(","))
+    }
+
+    fn match_details_reply(match_ids: &str) -> String {
+        let replies: Vec<String> = match_ids
+            .split(',')
+            .map(|x| format!("{{ \"Match\": {}, \"ret_msg\": null }}", x))
+            .collect();
+        format!("[{}]", replies.join(","))
+    }
+
+    // the first day pulls fine, but the details requests hit the daily limit after `allowed` calls
+    fn limited_reqwest(allowed: usize) -> ReqwestWrapper {
+        let details_calls = Arc::new(Mutex::new(0));
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().returning(move |x| {
+            let tail = x.rsplit('/').next().unwrap().to_string();
+            if x.contains("getmatchidsbyqueue") {
+                let ids: Vec<String> = (0..15).map(|i| format!("{}{:02}", x.contains("20190805") as u8 + 1, i)).collect();
+                let ids: Vec<&str> = ids.iter().map(|x| x.as_str()).collect();
+                return Ok(match_ids_reply(&ids));
+            }
+            let mut details_calls = details_calls.lock().unwrap();
+            *details_calls += 1;
+            if *details_calls > allowed {
+                return Ok(String::from("[{ \"ret_msg\": \"Daily request limit reached\" }]"));
+            }
+            Ok(match_details_reply(&tail))
+        });
+        reqwest
+    }
+
+    test resumes_after_daily_limit(temp_checkpoint) {
+        let start = Utc.with_ymd_and_hms(2019, 8, 4, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2019, 8, 6, 0, 0, 0).unwrap();
+        let mut fetched: Vec<u32> = Vec::new();
+
+        {
+            let request_maker = RequestMaker::mock(limited_reqwest(1));
+            let mut job = BulkJob::open(&request_maker, &temp_checkpoint.val, vec![DataConstants::RankedConquest], start, end)
+                .unwrap()
+                .chunk_size(2);
+            let status = job.run(|details| {
+                fetched.extend(details.into_iter().filter_map(|x| x.Match));
+                Ok(())
+            }).unwrap();
+
+            match status {
+                JobStatus::Paused(msg) => assert!(is_daily_limit(&msg)),
+                JobStatus::Complete => panic!("expected the job to pause"),
+            }
+            assert_eq!(job.checkpoint().windows_crawled, 2);
+            assert_eq!(job.checkpoint().batches_fetched, 1);
+            assert_eq!(job.checkpoint().pending_match_ids.len(), 20);
+        }
+
+        // a new run picks up from the checkpoint without crawling again
+        let request_maker = RequestMaker::mock(limited_reqwest(10));
+        let mut job = BulkJob::open(&request_maker, &temp_checkpoint.val, vec![DataConstants::RankedConquest], start, end).unwrap();
+        let status = job.run(|details| {
+            fetched.extend(details.into_iter().filter_map(|x| x.Match));
+            Ok(())
+        }).unwrap();
+
+        assert_eq!(status, JobStatus::Complete);
+        assert!(job.checkpoint().is_complete());
+        assert_eq!(job.checkpoint().windows_crawled, 2);
+        assert_eq!(job.checkpoint().discovered.len(), 30);
+        fetched.sort();
+        fetched.dedup();
+        assert_eq!(fetched.len(), 30);
+    }
+
+    test rejects_checkpoint_for_other_job(temp_checkpoint) {
+        let start = Utc.with_ymd_and_hms(2019, 8, 4, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2019, 8, 5, 0, 0, 0).unwrap();
+        Checkpoint::new(vec![DataConstants::RankedJoust], start, end).save(&temp_checkpoint.val).unwrap();
+
+        let request_maker = RequestMaker::mock(ReqwestWrapper::new());
+        assert!(BulkJob::open(&request_maker, &temp_checkpoint.val, vec![DataConstants::RankedDuel], start, end).is_err());
+    }
+
+    test splits_busy_days(temp_checkpoint) {
+        // the day is too busy to return anything, as is hour 3, and 3:10 repeats a match from 3:00
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().returning(|x| {
+            if !x.contains("getmatchidsbyqueue") {
+                return Ok(String::from("[]"));
+            }
+            Ok(match x.rsplit('/').next().unwrap() {
+                "5" => match_ids_reply(&["1", "2"]),
+                "3,00" => match_ids_reply(&["3", "4"]),
+                "3,10" => match_ids_reply(&["4", "5"]),
+                _ => match_ids_reply(&[]),
+            })
+        });
+        let request_maker = RequestMaker::mock(reqwest);
+        let start = Utc.with_ymd_and_hms(2019, 8, 5, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2019, 8, 6, 0, 0, 0).unwrap();
+
+        let mut job = BulkJob::open(&request_maker, &temp_checkpoint.val, vec![DataConstants::RankedConquest], start, end).unwrap();
+        assert_eq!(job.run(|_| Ok(())).unwrap(), JobStatus::Complete);
+
+        let mut discovered = job.checkpoint().discovered.clone();
+        discovered.sort();
+        assert_eq!(discovered, vec!["1", "2", "3", "4", "5"]);
+        assert!(job.checkpoint().failed_windows.is_empty());
+    }
+
+    test keeps_active_matches(temp_checkpoint) {
+        let start = Utc.with_ymd_and_hms(2019, 8, 4, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2019, 8, 5, 0, 0, 0).unwrap();
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().returning(|x| {
+            if x.contains("getmatchidsbyqueue") {
+                return Ok(String::from("[{ \"ret_msg\": null, \"Active_Flag\": \"n\", \"Match\": \"100\" }, \
+                    { \"ret_msg\": null, \"Active_Flag\": \"y\", \"Match\": \"101\" }]"));
+            }
+            Ok(match_details_reply(x.rsplit('/').next().unwrap()))
+        });
+        let request_maker = RequestMaker::mock(reqwest);
+
+        {
+            let mut job = BulkJob::open(&request_maker, &temp_checkpoint.val, vec![DataConstants::RankedConquest], start, end).unwrap();
+            assert_eq!(job.run(|_| Ok(())).unwrap(), JobStatus::Complete);
+        }
+
+        let job = BulkJob::open(&request_maker, &temp_checkpoint.val, vec![DataConstants::RankedConquest], start, end).unwrap();
+        assert_eq!(job.checkpoint().discovered, vec![String::from("100")]);
+        let active = job.active_matches();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].match_id, "101");
+        drop(job);
+
+        // still there if the tracker never got to report back
+        let mut job = BulkJob::open(&request_maker, &temp_checkpoint.val, vec![DataConstants::RankedConquest], start, end).unwrap();
+        assert_eq!(job.active_matches(), active);
+
+        job.add_finished(active).unwrap();
+        assert_eq!(job.checkpoint().pending_match_ids, vec![String::from("101")]);
+        assert!(job.checkpoint().active_matches.is_empty());
+    }
+
+    test expired_matches_are_dropped(temp_checkpoint) {
+        let start = Utc.with_ymd_and_hms(2019, 8, 4, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2019, 8, 5, 0, 0, 0).unwrap();
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().returning(|x| {
+            if x.contains("getmatchidsbyqueue") {
+                return Ok(String::from("[{ \"ret_msg\": null, \"Active_Flag\": \"y\", \"Match\": \"101\" }]"));
+            }
+            Ok(String::from("[]"))
+        });
+        let request_maker = RequestMaker::mock(reqwest);
+
+        let mut job = BulkJob::open(&request_maker, &temp_checkpoint.val, vec![DataConstants::RankedConquest], start, end).unwrap();
+        assert_eq!(job.run(|_| Ok(())).unwrap(), JobStatus::Complete);
+        let active = job.active_matches();
+        job.drop_expired(active).unwrap();
+        drop(job);
+
+        let job = BulkJob::open(&request_maker, &temp_checkpoint.val, vec![DataConstants::RankedConquest], start, end).unwrap();
+        assert!(job.active_matches().is_empty());
+        assert!(job.checkpoint().pending_match_ids.is_empty());
+    }
+
+    test checkpoint_open_errors_are_reported(temp_checkpoint) {
+        // a directory can't be read as a checkpoint, which must not look like a fresh job
+        fs::create_dir(&temp_checkpoint.val).unwrap();
+        let loaded = Checkpoint::load(&temp_checkpoint.val);
+        fs::remove_dir(&temp_checkpoint.val).unwrap();
+        assert!(loaded.is_err());
+        assert_eq!(Checkpoint::load(&temp_checkpoint.val), Ok(None));
+    }
+}
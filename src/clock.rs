@@ -0,0 +1,122 @@
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use std::sync::{Arc, Mutex};
+
+#[cfg(test)]
+use galvanic_test::test_suite;
+
+/*
+ * The source of the current time used when signing requests
+ */
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> DateTime<Utc> {
+        (**self).now()
+    }
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+// always reports the time it was last set to
+pub struct FixedClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl FixedClock {
+    pub fn new(now: DateTime<Utc>) -> FixedClock {
+        FixedClock {
+            now: Mutex::new(now),
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap() = now;
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
+
+/*
+ * Shifts another clock by how far it is behind or ahead of the HiRez servers
+ */
+pub struct SkewCorrectedClock {
+    clock: Box<dyn Clock>,
+    offset: Mutex<Duration>,
+}
+
+impl SkewCorrectedClock {
+    pub fn new(clock: Box<dyn Clock>) -> SkewCorrectedClock {
+        SkewCorrectedClock {
+            clock,
+            offset: Mutex::new(Duration::zero()),
+        }
+    }
+
+    pub fn offset(&self) -> Duration {
+        *self.offset.lock().unwrap()
+    }
+
+    pub fn set_offset(&self, offset: Duration) {
+        *self.offset.lock().unwrap() = offset;
+    }
+
+    // server_time is the time HiRez reported at roughly the current local time
+    pub fn observe_server_time(&self, server_time: DateTime<Utc>) {
+        *self.offset.lock().unwrap() = server_time.signed_duration_since(self.clock.now());
+    }
+}
+
+impl Clock for SkewCorrectedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.clock.now() + self.offset()
+    }
+}
+
+/*
+ * Parses timestamps like "8/21/2019 3:05:41 AM", as returned in CreateSessionReply
+ */
+pub fn parse_server_timestamp(timestamp: &str) -> Result<DateTime<Utc>, String> {
+    NaiveDateTime::parse_from_str(timestamp.trim(), "%m/%d/%Y %l:%M:%S %p")
+        .map(|x| Utc.from_utc_datetime(&x))
+        .map_err(|msg| format!("Invalid server timestamp {}: {}", timestamp, msg))
+}
+
+// the inverse of parse_server_timestamp
+pub fn format_server_timestamp(timestamp: &DateTime<Utc>) -> String {
+    timestamp.format("%-m/%-d/%Y %-I:%M:%S %p").to_string()
+}
+
+#[cfg(test)]
+test_suite! {
+    name test_clock;
+    use super::*;
+
+    test parse_server_timestamps() {
+        assert_eq!(parse_server_timestamp("8/21/2019 3:05:41 AM"), Ok(Utc.with_ymd_and_hms(2019, 8, 21, 3, 5, 41).unwrap()));
+        assert_eq!(parse_server_timestamp("12/1/2019 11:59:00 PM"), Ok(Utc.with_ymd_and_hms(2019, 12, 1, 23, 59, 0).unwrap()));
+        assert!(parse_server_timestamp("2019-08-21T03:05:41").is_err());
+        assert_eq!(format_server_timestamp(&Utc.with_ymd_and_hms(2019, 8, 5, 0, 6, 23).unwrap()), "8/5/2019 12:06:23 AM");
+    }
+
+    test skew_corrected_clock_applies_offset() {
+        let local_time = Utc.with_ymd_and_hms(2019, 8, 10, 12, 0, 0).unwrap();
+        let clock = SkewCorrectedClock::new(Box::new(FixedClock::new(local_time)));
+        assert_eq!(clock.now(), local_time);
+
+        clock.observe_server_time(Utc.with_ymd_and_hms(2019, 8, 10, 12, 5, 0).unwrap());
+        assert_eq!(clock.offset(), Duration::minutes(5));
+        assert_eq!(clock.now(), Utc.with_ymd_and_hms(2019, 8, 10, 12, 5, 0).unwrap());
+    }
+}
@@ -0,0 +1,402 @@
+use arrow::array::{Array, UInt32Array};
+use arrow::datatypes::FieldRef;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::{ArrowWriter, ProjectionMask};
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::{forward_to_deserialize_any, Deserializer, Serialize};
+use serde_arrow::schema::{SchemaLike, TracingOptions};
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::iter;
+use std::path::{Path, PathBuf};
+
+use crate::clock::parse_server_timestamp;
+use crate::models::{God, Item, PlayerMatchDetails};
+use crate::sink::Sink;
+
+#[cfg(test)]
+use galvanic_test::test_suite;
+
+/*
+ * Deserializes a model with placeholder values, its optional fields present down to some_depth
+ * and None below it
+ *
+ * decode::list reads whatever the format presents, so the models can't be traced from their
+ * types alone and are traced from these samples instead
+ */
+#[derive(Clone, Copy)]
+struct Placeholder<'a> {
+    depth: usize,
+    some_depth: usize,
+    // set if any optional field was left as None
+    truncated: &'a Cell<bool>,
+}
+
+macro_rules! zero {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                visitor.visit_u64(0)
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> Deserializer<'de> for Placeholder<'a> {
+    type Error = de::value::Error;
+
+    // only lists are read with deserialize_any, and a single item covers their columns
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    zero! { deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_f32(0.0)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_f64(0.0)
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_bool(false)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_char(' ')
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_str("")
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_str("")
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.depth < self.some_depth {
+            visitor.visit_some(Placeholder {
+                depth: self.depth + 1,
+                ..self
+            })
+        } else {
+            self.truncated.set(true);
+            visitor.visit_none()
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_seq(SeqDeserializer::new(iter::once(self)))
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_map(MapDeserializer::new(fields.iter().map(|x| (*x, self))))
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 bytes byte_buf unit unit_struct tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+impl<'de, 'a> IntoDeserializer<'de> for Placeholder<'a> {
+    type Deserializer = Placeholder<'a>;
+
+    fn into_deserializer(self) -> Placeholder<'a> {
+        self
+    }
+}
+
+// traces T from one sample per depth of optional fields, so every column is seen both ways
+fn trace<T: DeserializeOwned + Serialize>() -> Result<Vec<FieldRef>, String> {
+    let mut samples: Vec<T> = Vec::new();
+    for some_depth in 0.. {
+        let truncated = Cell::new(false);
+        let placeholder = Placeholder {
+            depth: 0,
+            some_depth,
+            truncated: &truncated,
+        };
+        samples.push(T::deserialize(placeholder).map_err(|msg| msg.to_string())?);
+        if !truncated.get() {
+            break;
+        }
+    }
+    Vec::<FieldRef>::from_samples(&samples, TracingOptions::default()).map_err(|msg| msg.to_string())
+}
+
+/*
+ * The columns of player_batch, one per PlayerMatchDetails field, all nullable except for
+ * those that are never missing
+ *
+ * The schema only depends on the models, so files written by different runs can be read together
+ */
+pub fn player_schema() -> Result<Vec<FieldRef>, String> {
+    trace::<PlayerMatchDetails>()
+}
+
+pub fn god_schema() -> Result<Vec<FieldRef>, String> {
+    trace::<God>()
+}
+
+pub fn item_schema() -> Result<Vec<FieldRef>, String> {
+    trace::<Item>()
+}
+
+fn to_batch<T: Serialize>(fields: &[FieldRef], rows: &[T]) -> Result<RecordBatch, String> {
+    serde_arrow::to_record_batch(fields, &rows)
+        .map_err(|msg| format!("Error converting to Arrow: {}", msg))
+}
+
+pub fn player_batch(rows: &[PlayerMatchDetails]) -> Result<RecordBatch, String> {
+    to_batch(&player_schema()?, rows)
+}
+
+pub fn god_batch(gods: &[God]) -> Result<RecordBatch, String> {
+    to_batch(&god_schema()?, gods)
+}
+
+pub fn item_batch(items: &[Item]) -> Result<RecordBatch, String> {
+    to_batch(&item_schema()?, items)
+}
+
+/*
+ * Writes batch to a snappy compressed Parquet file at path, replacing any already there
+ *
+ * The file is written next to path first, so path never holds a partial file
+ */
+pub fn write_parquet<P: AsRef<Path>>(path: P, batch: &RecordBatch) -> Result<(), String> {
+    let path = path.as_ref();
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+    let file = File::create(&temp_path)
+        .map_err(|msg| format!("Error creating {}: {}", temp_path.display(), msg))?;
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(properties))
+        .map_err(|msg| msg.to_string())?;
+    writer.write(batch).map_err(|msg| msg.to_string())?;
+    writer.close().map_err(|msg| msg.to_string())?;
+    fs::rename(&temp_path, path)
+        .map_err(|msg| format!("Error renaming {}: {}", temp_path.display(), msg))
+}
+
+// the match ids in every Parquet file in dir
+fn written_matches(dir: &Path) -> Result<HashSet<u32>, String> {
+    let mut matches = HashSet::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(matches),
+        Err(msg) => return Err(format!("Error reading {}: {}", dir.display(), msg)),
+    };
+
+    for entry in entries {
+        let path = entry.map_err(|msg| msg.to_string())?.path();
+        if path.extension() != Some(OsStr::new("parquet")) {
+            continue;
+        }
+        let file =
+            File::open(&path).map_err(|msg| format!("Error opening {}: {}", path.display(), msg))?;
+        let builder =
+            ParquetRecordBatchReaderBuilder::try_new(file).map_err(|msg| msg.to_string())?;
+        let index = builder
+            .schema()
+            .index_of("Match")
+            .map_err(|_| format!("{} has no Match column", path.display()))?;
+        let mask = ProjectionMask::roots(builder.parquet_schema(), [index]);
+        let reader = builder
+            .with_projection(mask)
+            .build()
+            .map_err(|msg| msg.to_string())?;
+
+        for batch in reader {
+            let batch = batch.map_err(|msg| msg.to_string())?;
+            let column = batch
+                .column_by_name("Match")
+                .and_then(|x| x.as_any().downcast_ref::<UInt32Array>())
+                .ok_or_else(|| format!("{} has no Match column", path.display()))?;
+            matches.extend(column.iter().flatten());
+        }
+    }
+    Ok(matches)
+}
+
+// the queue=.../date=... directory a row belongs in, relative to the dataset
+fn partition(row: &PlayerMatchDetails) -> PathBuf {
+    let queue = match row.match_queue_id {
+        Some(queue_id) => queue_id.to_string(),
+        None => String::from("unknown"),
+    };
+    let date = row
+        .Entry_Datetime
+        .as_ref()
+        .and_then(|x| parse_server_timestamp(x).ok())
+        .map(|x| x.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| String::from("unknown"));
+
+    PathBuf::from(format!("queue={}", queue)).join(format!("date={}", date))
+}
+
+/*
+ * Writes match details as a Parquet dataset partitioned by queue id and match date, in the
+ * hive layout most data frame libraries read directly
+ *
+ * Each write adds a file per partition named after its first match id. Matches already in a
+ * partition are skipped, so those written again by a resumed BulkJob, which may batch them
+ * differently, aren't duplicated
+ */
+pub struct ParquetSink {
+    dir: PathBuf,
+    schema: Vec<FieldRef>,
+    // the matches in each partition, read from its files the first time it is written to
+    written: HashMap<PathBuf, HashSet<u32>>,
+}
+
+impl ParquetSink {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<ParquetSink, String> {
+        Ok(ParquetSink {
+            dir: dir.as_ref().to_path_buf(),
+            schema: player_schema()?,
+            written: HashMap::new(),
+        })
+    }
+}
+
+impl Sink for ParquetSink {
+    // rows without a match id, which only carry a ret_msg, are skipped
+    fn write(&mut self, details: &[PlayerMatchDetails]) -> Result<(), String> {
+        let mut partitions: BTreeMap<PathBuf, Vec<&PlayerMatchDetails>> = BTreeMap::new();
+        for row in details.iter().filter(|x| x.Match.is_some()) {
+            partitions.entry(partition(row)).or_default().push(row);
+        }
+
+        for (partition, mut rows) in partitions {
+            let dir = self.dir.join(partition);
+            if !self.written.contains_key(&dir) {
+                let written = written_matches(&dir)?;
+                self.written.insert(dir.clone(), written);
+            }
+            let written = self.written.get_mut(&dir).unwrap();
+            rows.retain(|x| !written.contains(&x.Match.unwrap()));
+            if rows.is_empty() {
+                continue;
+            }
+
+            fs::create_dir_all(&dir)
+                .map_err(|msg| format!("Error creating {}: {}", dir.display(), msg))?;
+            let batch = to_batch(&self.schema, &rows)?;
+            let first_match = rows.iter().filter_map(|x| x.Match).min().unwrap();
+            write_parquet(dir.join(format!("part-{}.parquet", first_match)), &batch)?;
+            written.extend(rows.iter().filter_map(|x| x.Match));
+        }
+        Ok(())
+    }
+
+    // every write closes the files it made
+    fn flush(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+test_suite! {
+    name test_columnar;
+    use super::*;
+    use arrow::array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::env;
+    use crate::test_responses;
+
+    test schema_is_stable() {
+        let rows: Vec<PlayerMatchDetails> = serde_json::from_str(test_responses::GET_MATCH_DETAILS).unwrap();
+        let batch = player_batch(&rows).unwrap();
+
+        assert_eq!(batch.num_rows(), 10);
+        // the schema comes from the model, not from which fields were null in the rows
+        assert_eq!(batch.schema().fields().len(), player_schema().unwrap().len());
+        let ban1 = batch.schema().field_with_name("Ban1").unwrap().clone();
+        assert!(ban1.is_nullable());
+        assert!(batch.column_by_name("MergedPlayers").unwrap().is_null(0));
+
+        assert!(god_schema().unwrap().iter().any(|x| x.name() == "Ability_5"));
+        assert_eq!(item_batch(&[]).unwrap().num_rows(), 0);
+    }
+
+    test sink_partitions_by_queue_and_date() {
+        let dir = env::temp_dir().join(format!("hirust-{}-parquet", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let rows: Vec<PlayerMatchDetails> = serde_json::from_str(test_responses::GET_MATCH_DETAILS).unwrap();
+
+        let mut sink = ParquetSink::new(&dir).unwrap();
+        sink.write(&rows).unwrap();
+        sink.write(&rows).unwrap();
+
+        let partition = dir.join("queue=451").join("date=2019-08-05");
+        let files: Vec<PathBuf> = fs::read_dir(&partition).unwrap().map(|x| x.unwrap().path()).collect();
+        assert_eq!(files, vec![partition.join("part-956598608.parquet")]);
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&files[0]).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let num_rows: usize = reader.map(|x| x.unwrap().num_rows()).sum();
+        assert_eq!(num_rows, 10);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    test sink_skips_matches_already_written() {
+        let dir = env::temp_dir().join(format!("hirust-{}-parquet-resume", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let rows: Vec<PlayerMatchDetails> = serde_json::from_str(test_responses::GET_MATCH_DETAILS).unwrap();
+        let mut later_rows = rows.clone();
+        for row in later_rows.iter_mut() {
+            row.Match = Some(956598607);
+        }
+
+        ParquetSink::new(&dir).unwrap().write(&rows).unwrap();
+        // a resumed job batches the first match with one that sorts before it
+        let mut regrouped = later_rows.clone();
+        regrouped.extend(rows.iter().cloned());
+        ParquetSink::new(&dir).unwrap().write(&regrouped).unwrap();
+
+        let partition = dir.join("queue=451").join("date=2019-08-05");
+        let mut matches: Vec<u32> = written_matches(&partition).unwrap().into_iter().collect();
+        matches.sort();
+        assert_eq!(matches, vec![956598607, 956598608]);
+        let num_rows: usize = fs::read_dir(&partition)
+            .unwrap()
+            .map(|x| {
+                let file = File::open(x.unwrap().path()).unwrap();
+                let reader = ParquetRecordBatchReaderBuilder::try_new(file).unwrap().build().unwrap();
+                reader.map(|x| x.unwrap().num_rows()).sum::<usize>()
+            })
+            .sum();
+        assert_eq!(num_rows, 20);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
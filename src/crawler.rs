@@ -0,0 +1,262 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::HashSet;
+
+use crate::endpoints::{GetMatchIdsByQueueRequest, TimeWindow};
+use crate::hi_rez_constants::DataConstants;
+use crate::request_maker::RequestMaker;
+
+#[cfg(test)]
+use galvanic_test::test_suite;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CrawledMatchId {
+    pub match_id: String,
+    pub queue_id: DataConstants,
+    pub date: NaiveDate,
+    // the window the match id was retrieved from, after any splitting
+    pub window: TimeWindow,
+}
+
+#[derive(Debug, Default)]
+pub struct CrawlResult {
+    pub match_ids: Vec<CrawledMatchId>,
+    // matches still being played, which a PendingMatchTracker can fetch once they finish
+    pub active_match_ids: Vec<CrawledMatchId>,
+    // requests that failed outright, which are not retried
+    pub errors: Vec<(GetMatchIdsByQueueRequest, String)>,
+    // ten minute windows that still reached the truncation limit, so may be missing matches
+    pub truncated: Vec<GetMatchIdsByQueueRequest>,
+}
+
+/*
+ * Retrieves the ids of completed matches over a range of dates
+ *
+ * Whole days are requested first. Windows that reach the truncation limit are requested again
+ * as hours and then as ten minute windows. HiRez can also return nothing for a window with too
+ * many matches, so empty windows can be split too
+ */
+pub struct MatchIdCrawler<'a> {
+    request_maker: &'a RequestMaker,
+    truncation_limit: Option<usize>,
+    split_empty: bool,
+}
+
+impl<'a> MatchIdCrawler<'a> {
+    pub fn new(request_maker: &'a RequestMaker) -> MatchIdCrawler<'a> {
+        MatchIdCrawler {
+            request_maker,
+            truncation_limit: None,
+            split_empty: false,
+        }
+    }
+
+    // windows with at least this many replies are assumed to be cut short
+    pub fn truncation_limit(mut self, limit: usize) -> MatchIdCrawler<'a> {
+        self.truncation_limit = Some(limit);
+        self
+    }
+
+    // whether empty windows are split, which is wasteful for queues that are rarely played
+    pub fn split_empty(mut self, split_empty: bool) -> MatchIdCrawler<'a> {
+        self.split_empty = split_empty;
+        self
+    }
+
+    fn is_truncated(&self, num_replies: usize) -> bool {
+        self.truncation_limit.is_some_and(|x| num_replies >= x)
+    }
+
+    fn is_suspicious(&self, num_replies: usize) -> bool {
+        (self.split_empty && num_replies == 0) || self.is_truncated(num_replies)
+    }
+
+    pub fn crawl(
+        &self,
+        queues: Vec<DataConstants>,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> CrawlResult {
+        let mut pending: Vec<GetMatchIdsByQueueRequest> = queues
+            .into_iter()
+            .flat_map(|queue_id| GetMatchIdsByQueueRequest::for_range(queue_id, start, end))
+            .collect();
+        let mut result = CrawlResult::default();
+        let mut seen: HashSet<String> = HashSet::new();
+
+        // each pass requests the windows split in the one before
+        while !pending.is_empty() {
+            pending = self.crawl_windows(pending, &mut seen, &mut result);
+        }
+
+        result
+    }
+
+    /*
+     * Requests each window once, adding match ids not already seen to result and returning the
+     * windows that need splitting
+     */
+    pub fn crawl_windows(
+        &self,
+        requests: Vec<GetMatchIdsByQueueRequest>,
+        seen: &mut HashSet<String>,
+        result: &mut CrawlResult,
+    ) -> Vec<GetMatchIdsByQueueRequest> {
+        let replies = self.request_maker.execute(requests.clone());
+        let mut split = Vec::new();
+
+        for (request, reply) in requests.into_iter().zip(replies) {
+            let replies = match reply {
+                Ok(replies) => replies,
+                Err(msg) => {
+                    result.errors.push((request, msg));
+                    continue;
+                }
+            };
+            if let Some(msg) = replies.first().and_then(|x| x.ret_msg.clone()) {
+                result
+                    .errors
+                    .push((request, format!("GetMatchIdsByQueue Request Error: {}", msg)));
+                continue;
+            }
+
+            let windows = request.window.subdivide();
+            if self.is_suspicious(replies.len()) && !windows.is_empty() {
+                split.extend(windows.into_iter().map(|window| GetMatchIdsByQueueRequest {
+                    window,
+                    ..request.clone()
+                }));
+                continue;
+            }
+            if self.is_truncated(replies.len()) {
+                result.truncated.push(request.clone());
+            }
+
+            for reply in replies {
+                let (match_ids, match_id) = match (reply.Active_Flag, reply.Match) {
+                    (Some('n'), Some(match_id)) => (&mut result.match_ids, match_id),
+                    (Some('y'), Some(match_id)) => (&mut result.active_match_ids, match_id),
+                    _ => continue,
+                };
+                if seen.insert(match_id.clone()) {
+                    match_ids.push(CrawledMatchId {
+                        match_id,
+                        queue_id: request.queue_id,
+                        date: request.date,
+                        window: request.window,
+                    });
+                }
+            }
+        }
+
+        split
+    }
+}
+
+#[cfg(test)]
+test_suite! {
+    name test_crawler;
+    use super::*;
+    use chrono::TimeZone;
+    use crate::reqwest_wrapper::MockWrapper as ReqwestWrapper;
+
+    fn match_ids_reply(match_ids: &[&str]) -> String {
+        let replies: Vec<String> = match_ids
+            .iter()
+            .map(|x| format!("{{ \"ret_msg\": null, \"Active_Flag\": \"n\", \"Match\": \"{}\" }}", x))
+            .collect();
+        format!("[{}]", replies.join(","))
+    }
+
+    // the day is too busy to return anything, as is hour 3, and 3:10 repeats a match from 3:00
+    fn busy_day_reqwest() -> ReqwestWrapper {
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().returning(|x| {
+            let window = x.rsplit('/').next().unwrap();
+            Ok(match window {
+                "5" => match_ids_reply(&["1", "2"]),
+                "3,00" => match_ids_reply(&["3", "4"]),
+                "3,10" => match_ids_reply(&["4", "5"]),
+                _ => match_ids_reply(&[]),
+            })
+        });
+        reqwest
+    }
+
+    test crawl_splits_empty_windows() {
+        let request_maker = RequestMaker::mock(busy_day_reqwest());
+        let result = MatchIdCrawler::new(&request_maker).split_empty(true).crawl(
+            vec![DataConstants::RankedConquest],
+            Utc.with_ymd_and_hms(2019, 8, 5, 0, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2019, 8, 6, 0, 0, 0).unwrap(),
+        );
+
+        assert!(result.errors.is_empty());
+        let found: Vec<(&str, TimeWindow)> = result
+            .match_ids
+            .iter()
+            .map(|x| (x.match_id.as_str(), x.window))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("1", TimeWindow::hour(5).unwrap()),
+                ("2", TimeWindow::hour(5).unwrap()),
+                ("3", TimeWindow::ten_minutes(3, 0).unwrap()),
+                ("4", TimeWindow::ten_minutes(3, 0).unwrap()),
+                ("5", TimeWindow::ten_minutes(3, 10).unwrap()),
+            ]
+        );
+        assert!(result.match_ids.iter().all(|x| x.date == NaiveDate::from_ymd_opt(2019, 8, 5).unwrap()));
+    }
+
+    test crawl_splits_truncated_windows() {
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().returning(|x| {
+            let window = x.rsplit('/').next().unwrap();
+            Ok(match window {
+                "-1" => match_ids_reply(&["1", "2"]),
+                "7" => match_ids_reply(&["1", "3"]),
+                "8" => match_ids_reply(&["2"]),
+                "7,00" => match_ids_reply(&["1", "3"]),
+                _ => match_ids_reply(&[]),
+            })
+        });
+        let request_maker = RequestMaker::mock(reqwest);
+        let result = MatchIdCrawler::new(&request_maker)
+            .truncation_limit(2)
+            .crawl(
+                vec![DataConstants::RankedJoust],
+                Utc.with_ymd_and_hms(2019, 8, 5, 0, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2019, 8, 6, 0, 0, 0).unwrap(),
+            );
+
+        // the day and hour 7 reached the limit, but 7:00 can't be split any further
+        let found: Vec<(&str, TimeWindow)> = result
+            .match_ids
+            .iter()
+            .map(|x| (x.match_id.as_str(), x.window))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("2", TimeWindow::hour(8).unwrap()),
+                ("1", TimeWindow::ten_minutes(7, 0).unwrap()),
+                ("3", TimeWindow::ten_minutes(7, 0).unwrap()),
+            ]
+        );
+        let truncated: Vec<TimeWindow> = result.truncated.iter().map(|x| x.window).collect();
+        assert_eq!(truncated, vec![TimeWindow::ten_minutes(7, 0).unwrap()]);
+    }
+
+    test crawl_keeps_empty_windows_by_default() {
+        let request_maker = RequestMaker::mock(busy_day_reqwest());
+        let result = MatchIdCrawler::new(&request_maker).crawl(
+            vec![DataConstants::RankedConquest],
+            Utc.with_ymd_and_hms(2019, 8, 5, 0, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2019, 8, 6, 0, 0, 0).unwrap(),
+        );
+
+        assert!(result.match_ids.is_empty());
+        assert!(result.truncated.is_empty());
+    }
+}
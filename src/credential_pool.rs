@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::hi_rez_constants::ReturnDataType;
+use crate::session_manager::{SessionManager, SessionProvider, SessionWaiter, SHARED_POLL_INTERVAL};
+
+cfg_if::cfg_if! {
+    if #[cfg(test)] {
+        use galvanic_test::test_suite;
+    } else {
+        use crate::hi_rez_constants::UrlConstants;
+        use crate::session_manager::Auth;
+        use crate::session_store::FileSessionStore;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PoolStrategy {
+    // take turns between the credentials
+    RoundRobin,
+    // prefer the credential with the fewest sessions in use, then the fewest requests spent today
+    LeastLoaded,
+}
+
+/*
+ * Spreads sessions across several dev ids, each with its own SessionManager and daily limits
+ */
+pub struct CredentialPool {
+    session_managers: Vec<SessionManager>,
+    strategy: PoolStrategy,
+    next_manager: Mutex<usize>,
+    // the index of the SessionManager each active session came from
+    session_owners: Mutex<HashMap<String, usize>>,
+    session_waiter: SessionWaiter,
+}
+
+impl CredentialPool {
+    /*
+     * Creates a SessionManager per credential, storing each one's sessions in sessions-<dev id>.json
+     */
+    #[cfg(not(test))]
+    pub fn new(
+        credentials: Vec<Auth>,
+        base_url: UrlConstants,
+        strategy: PoolStrategy,
+    ) -> Result<CredentialPool, String> {
+        let mut session_managers = Vec::new();
+        for auth in credentials {
+            let session_store = FileSessionStore::new(format!("sessions-{}.json", auth.dev_id));
+            session_managers.push(SessionManager::with_store(
+                auth,
+                base_url.clone(),
+                Box::new(session_store),
+            )?);
+        }
+        Ok(CredentialPool::from_managers(session_managers, strategy))
+    }
+
+    pub fn from_managers(
+        session_managers: Vec<SessionManager>,
+        strategy: PoolStrategy,
+    ) -> CredentialPool {
+        CredentialPool {
+            session_managers,
+            strategy,
+            next_manager: Mutex::new(0),
+            session_owners: Mutex::new(HashMap::new()),
+            session_waiter: SessionWaiter::new(),
+        }
+    }
+
+    pub fn session_managers(&self) -> &[SessionManager] {
+        &self.session_managers
+    }
+
+    // the order in which the SessionManagers are asked for a session
+    fn candidates(&self) -> Vec<usize> {
+        let num_managers = self.session_managers.len();
+        match self.strategy {
+            PoolStrategy::RoundRobin => {
+                let mut next_manager = self.next_manager.lock().unwrap();
+                let first = *next_manager;
+                *next_manager = (first + 1) % num_managers;
+                (0..num_managers).map(|x| (first + x) % num_managers).collect()
+            }
+            PoolStrategy::LeastLoaded => {
+                let mut candidates: Vec<(usize, u16, usize)> = self
+                    .session_managers
+                    .iter()
+                    .enumerate()
+                    .map(|(i, x)| (x.active_session_count(), x.counters().num_requests, i))
+                    .collect();
+                candidates.sort();
+                candidates.into_iter().map(|(_, _, i)| i).collect()
+            }
+        }
+    }
+
+    /*
+     * Retrieves a session from the first SessionManager with one to spare
+     */
+    pub fn get_session_key(&self) -> Result<String, String> {
+        if self.session_managers.is_empty() {
+            return Err(String::from("Credential pool is empty"));
+        }
+
+        let mut limit_messages: Vec<String> = Vec::new();
+        for index in self.candidates() {
+            let session_manager = &self.session_managers[index];
+            match session_manager.get_session_key() {
+                Ok(key) => {
+                    self.session_owners.lock().unwrap().insert(key.clone(), index);
+                    return Ok(key);
+                }
+                Err(msg) => {
+                    if msg != "No sessions available" {
+                        limit_messages.push(format!("{}: {}", session_manager.credentials.dev_id, msg));
+                    }
+                }
+            }
+        }
+
+        // only give up once every credential has hit a daily limit
+        if limit_messages.len() < self.session_managers.len() {
+            Err(String::from("No sessions available"))
+        } else {
+            Err(format!(
+                "No credentials available | {}",
+                limit_messages.join(" | ")
+            ))
+        }
+    }
+
+    /*
+     * Retrieves a session, waiting until one is returned to the pool if all are in use
+     */
+    pub fn get_session_key_timeout(&self, timeout: Option<Duration>) -> Result<String, String> {
+        let deadline = timeout.map(|x| Instant::now() + x);
+        let poll_interval = if self.session_managers.iter().any(|x| x.is_shared()) {
+            Some(SHARED_POLL_INTERVAL)
+        } else {
+            None
+        };
+
+        loop {
+            let seen_releases = self.session_waiter.releases();
+            match self.get_session_key() {
+                Ok(key) => return Ok(key),
+                Err(msg) => {
+                    if msg != "No sessions available" {
+                        return Err(msg);
+                    }
+                }
+            };
+
+            if !self.session_waiter.wait(seen_releases, deadline, poll_interval) {
+                return Err(String::from("Timed out waiting for a session"));
+            }
+        }
+    }
+
+    fn owner(&self, session_key: &str) -> Option<usize> {
+        self.session_owners.lock().unwrap().get(session_key).cloned()
+    }
+}
+
+impl SessionProvider for CredentialPool {
+    fn get_session_key_concurrent(&self) -> Result<String, String> {
+        self.get_session_key_timeout(None)
+    }
+
+    fn replace_session(&self, session_key: String) {
+        let owner = self.session_owners.lock().unwrap().remove(&session_key);
+        match owner {
+            Some(index) => self.session_managers[index].replace_session(session_key),
+            None => println!("Session not leased from credential pool: {}", session_key),
+        }
+        self.session_waiter.notify();
+    }
+
+    fn remove_invalid_session(&self, session_key: String) {
+        let owner = self.session_owners.lock().unwrap().remove(&session_key);
+        match owner {
+            Some(index) => self.session_managers[index].remove_invalid_session(session_key),
+            None => println!("Session not leased from credential pool: {}", session_key),
+        }
+        self.session_waiter.notify();
+    }
+
+    fn url(
+        &self,
+        session_key: &str,
+        method_name: &str,
+        data_type: &ReturnDataType,
+        method_specific: &str,
+    ) -> Result<String, String> {
+        match self.owner(session_key) {
+            Some(index) => SessionProvider::url(
+                &self.session_managers[index],
+                session_key,
+                method_name,
+                data_type,
+                method_specific,
+            ),
+            None => Err(format!(
+                "Session not leased from credential pool: {}",
+                session_key
+            )),
+        }
+    }
+
+    fn max_concurrent_sessions(&self) -> usize {
+        self.session_managers
+            .iter()
+            .map(|x| x.max_concurrent_sessions())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+test_suite! {
+    name test_credential_pool;
+    use super::*;
+    use chrono::Utc;
+    use crate::reqwest_wrapper::MockWrapper as ReqwestWrapper;
+    use crate::session_manager::DailyCounters;
+    use crate::session_store::{MemorySessionStore, SessionState, SESSION_STORE_VERSION};
+
+    fn approving_manager(session_id: &'static str) -> SessionManager {
+        let mut reqwest = ReqwestWrapper::new();
+        let mut count = 0;
+        reqwest.expect_get_to_text().returning(move |_x| {
+            count += 1;
+            Ok(format!(
+                "{{ \"ret_msg\": \"Approved\", \"session_id\": \"{}-{}\", \"timestamp\": null }}",
+                session_id, count
+            ))
+        });
+        SessionManager::mock(reqwest)
+    }
+
+    fn exhausted_manager() -> SessionManager {
+        let session_store = MemorySessionStore::with_state(SessionState {
+            version: SESSION_STORE_VERSION,
+            sessions: Vec::new(),
+            counters: Some(DailyCounters {
+                day: Utc::now().date_naive(),
+                sessions_created: 0,
+                num_requests: 7500,
+            }),
+            creating: Vec::new(),
+        });
+        SessionManager::mock_with_store(ReqwestWrapper::new(), Box::new(session_store))
+    }
+
+    test round_robin_alternates_credentials() {
+        let pool = CredentialPool::from_managers(
+            vec![approving_manager("first"), approving_manager("second")],
+            PoolStrategy::RoundRobin,
+        );
+
+        let first_key = pool.get_session_key().unwrap();
+        let second_key = pool.get_session_key().unwrap();
+        assert_eq!(first_key, "first-1");
+        assert_eq!(second_key, "second-1");
+        assert_eq!(pool.max_concurrent_sessions(), 90);
+
+        SessionProvider::replace_session(&pool, first_key);
+        assert_eq!(pool.session_managers()[0].active_session_count(), 0);
+        assert_eq!(pool.session_managers()[1].active_session_count(), 1);
+
+        let url = SessionProvider::url(&pool, &second_key, "getgods", &ReturnDataType::Json, "/1").unwrap();
+        assert!(url.contains("/second-1/"));
+        assert!(SessionProvider::url(&pool, "unknown", "getgods", &ReturnDataType::Json, "/1").is_err());
+    }
+
+    test least_loaded_prefers_idle_credentials() {
+        let pool = CredentialPool::from_managers(
+            vec![approving_manager("first"), approving_manager("second")],
+            PoolStrategy::LeastLoaded,
+        );
+
+        assert_eq!(pool.get_session_key().unwrap(), "first-1");
+        assert_eq!(pool.get_session_key().unwrap(), "second-1");
+        assert_eq!(pool.get_session_key().unwrap(), "first-2");
+    }
+
+    test exhausted_credentials_are_skipped() {
+        let pool = CredentialPool::from_managers(
+            vec![exhausted_manager(), approving_manager("second")],
+            PoolStrategy::RoundRobin,
+        );
+
+        assert_eq!(pool.get_session_key().unwrap(), "second-1");
+        assert_eq!(pool.get_session_key().unwrap(), "second-2");
+    }
+
+    test all_exhausted_is_an_error() {
+        let pool = CredentialPool::from_managers(
+            vec![exhausted_manager(), exhausted_manager()],
+            PoolStrategy::RoundRobin,
+        );
+
+        let msg = SessionProvider::get_session_key_concurrent(&pool).unwrap_err();
+        assert!(msg.starts_with("No credentials available"));
+    }
+}
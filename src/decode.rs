@@ -0,0 +1,329 @@
+use serde::de::{self, DeserializeOwned, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::drift::{error_steps, field_name, missing_field, value_at};
+use crate::hi_rez_constants::ReturnDataType;
+
+#[cfg(feature = "xml")]
+use quick_xml::events::{BytesStart, Event};
+#[cfg(test)]
+use galvanic_test::test_suite;
+
+// the most fields repaired in a single reply before giving up on it
+const MAX_REPAIRS: usize = 100;
+
+/*
+ * Deserializes a reply requested with data_type into the same models either way
+ */
+pub fn decode<T: DeserializeOwned>(data_type: &ReturnDataType, text: &str) -> Result<T, String> {
+    decode_lenient(data_type, text).map(|(decoded, _)| decoded)
+}
+
+/*
+ * Like decode, but also returns the fields that had to be left empty for the reply to fit
+ *
+ * JSON numbers sent as strings, or as whole floats, are read as numbers. Numbers that don't fit
+ * their field, and other values of the wrong type, are recorded and read as null so one bad
+ * field doesn't lose the whole reply. XML replies are read strictly
+ */
+pub fn decode_lenient<T: DeserializeOwned>(
+    data_type: &ReturnDataType,
+    text: &str,
+) -> Result<(T, Vec<Anomaly>), String> {
+    match data_type {
+        ReturnDataType::Json => decode_json(text),
+        ReturnDataType::Xml => decode_xml(text).map(|decoded| (decoded, Vec::new())),
+    }
+}
+
+// a field that didn't fit its model and was read as null instead
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Anomaly {
+    pub field: String,
+    // as it appeared in the reply
+    pub value: String,
+    pub reason: String,
+}
+
+// a number written as text, or None if it isn't one
+fn parse_number(text: &str) -> Option<Value> {
+    let text = text.trim();
+    if let Ok(number) = text.parse::<u64>() {
+        Some(Value::from(number))
+    } else if let Ok(number) = text.parse::<i64>() {
+        Some(Value::from(number))
+    } else {
+        text.parse::<f64>()
+            .ok()
+            .filter(|x| x.is_finite())
+            .map(Value::from)
+    }
+}
+
+fn decode_json<T: DeserializeOwned>(text: &str) -> Result<(T, Vec<Anomaly>), String> {
+    let strict_error = match serde_json::from_str(text) {
+        Ok(decoded) => return Ok((decoded, Vec::new())),
+        Err(msg) => msg.to_string(),
+    };
+    // replies that aren't JSON at all can't be repaired
+    let mut raw: Value = serde_json::from_str(text).map_err(|_| strict_error.clone())?;
+    let mut anomalies = Vec::new();
+
+    // each pass repairs the first field that didn't fit
+    for _ in 0..MAX_REPAIRS {
+        let err = match serde_path_to_error::deserialize::<_, T>(&raw) {
+            Ok(decoded) => return Ok((decoded, anomalies)),
+            Err(err) => err,
+        };
+        let reason = err.inner().to_string();
+        let steps = error_steps(err.path());
+        let value = match value_at(&mut raw, &steps) {
+            Some(value) if missing_field(&reason).is_none() => value,
+            _ => break,
+        };
+
+        let repaired = match value {
+            // a required field can't be left empty
+            Value::Null => break,
+            Value::String(text) if text.trim().is_empty() => Some(Value::Null),
+            Value::String(text) => parse_number(text),
+            Value::Number(number) if reason.contains("expected a string") => {
+                Some(Value::String(number.to_string()))
+            }
+            Value::Number(number) => number
+                .as_f64()
+                .filter(|x| x.fract() == 0.0 && number.is_f64() && x.abs() < 1e15)
+                .map(|x| Value::from(x as i64)),
+            _ => None,
+        };
+        *value = match repaired {
+            Some(repaired) => repaired,
+            None => {
+                anomalies.push(Anomaly {
+                    field: field_name(&steps),
+                    value: match value {
+                        Value::String(text) => text.clone(),
+                        _ => value.to_string(),
+                    },
+                    reason,
+                });
+                Value::Null
+            }
+        };
+    }
+
+    Err(strict_error)
+}
+
+#[cfg(feature = "xml")]
+fn decode_xml<T: DeserializeOwned>(text: &str) -> Result<T, String> {
+    let elements = unwrap_xml_array(text)?;
+    quick_xml::de::from_str(&elements).map_err(|msg| msg.to_string())
+}
+
+#[cfg(not(feature = "xml"))]
+fn decode_xml<T: DeserializeOwned>(_text: &str) -> Result<T, String> {
+    Err(String::from("HiRust was built without the xml feature"))
+}
+
+#[cfg(feature = "xml")]
+fn is_nil(element: &BytesStart) -> bool {
+    element.attributes().filter_map(|x| x.ok()).any(|x| {
+        x.key.local_name().as_ref() == b"nil" && x.value.as_ref() == b"true"
+    })
+}
+
+/*
+ * HiRez wraps every reply in an ArrayOf... element and marks nulls with i:nil="true"
+ *
+ * Strips both, leaving the array items as a sequence of top level elements with nulls absent
+ */
+#[cfg(feature = "xml")]
+fn unwrap_xml_array(text: &str) -> Result<String, String> {
+    let mut reader = quick_xml::Reader::from_str(text);
+    let mut writer = quick_xml::Writer::new(Vec::new());
+    let mut depth = 0;
+    // the depth of the nil element being skipped, if any
+    let mut skipping: Option<usize> = None;
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|msg| format!("Invalid XML at {}: {}", reader.buffer_position(), msg))?;
+        let write = match event {
+            Event::Start(ref element) => {
+                depth += 1;
+                if skipping.is_none() && is_nil(element) {
+                    skipping = Some(depth);
+                }
+                depth > 1
+            }
+            Event::End(_) => {
+                depth -= 1;
+                if skipping == Some(depth + 1) {
+                    skipping = None;
+                    false
+                } else {
+                    depth > 0
+                }
+            }
+            Event::Empty(ref element) => depth > 0 && !is_nil(element),
+            Event::Text(_) | Event::CData(_) => depth > 1,
+            Event::Eof => break,
+            _ => false,
+        };
+
+        if write && skipping.is_none() {
+            writer
+                .write_event(event)
+                .map_err(|msg| format!("Error rewriting XML: {}", msg))?;
+        }
+    }
+
+    String::from_utf8(writer.into_inner()).map_err(|msg| msg.to_string())
+}
+
+/*
+ * Reads a list field, which is a JSON array or, in XML, an element wrapping one child per item
+ *
+ * quick-xml presents the wrapping element as a map, so the format decides which is read
+ */
+pub(crate) fn list<'de, D, T>(deserializer: D) -> Result<Option<Vec<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct ListVisitor<T>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for ListVisitor<T> {
+        type Value = Option<Vec<T>>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a list")
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+            deserializer.deserialize_any(self)
+        }
+
+        // an empty XML element
+        fn visit_str<E: de::Error>(self, _value: &str) -> Result<Self::Value, E> {
+            Ok(Some(Vec::new()))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut items = Vec::new();
+            while let Some(item) = seq.next_element()? {
+                items.push(item);
+            }
+            Ok(Some(items))
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut items = Vec::new();
+            while let Some((_, item)) = map.next_entry::<IgnoredAny, T>()? {
+                items.push(item);
+            }
+            Ok(Some(items))
+        }
+    }
+
+    deserializer.deserialize_option(ListVisitor(PhantomData))
+}
+
+#[cfg(test)]
+test_suite! {
+    name test_decode;
+    use super::*;
+    use crate::models::{GetMatchIdsByQueueReply, Item, PlayerMatchDetails};
+
+    test decode_json() {
+        let replies: Vec<GetMatchIdsByQueueReply> = decode(
+            &ReturnDataType::Json,
+            "[{ \"ret_msg\": null, \"Active_Flag\": \"n\", \"Match\": \"956598608\" }]",
+        ).unwrap();
+        assert_eq!(replies[0].Match, Some(String::from("956598608")));
+    }
+
+    test lenient_numbers() {
+        let text = "[{ \"Match\": \"956598608\", \"Kills_Player\": 300, \"Deaths\": 2.0, \
+                     \"Kills_Double\": 70000, \"Assists\": \"lots\", \"Gold_Earned\": \" \", \"playerId\": 12 }]";
+        let (replies, anomalies): (Vec<PlayerMatchDetails>, Vec<Anomaly>) =
+            decode_lenient(&ReturnDataType::Json, text).unwrap();
+
+        assert_eq!(replies[0].Match, Some(956598608));
+        assert_eq!(replies[0].Deaths, Some(2));
+        assert_eq!(replies[0].Gold_Earned, None);
+        assert_eq!(replies[0].playerId, Some(String::from("12")));
+        assert_eq!(replies[0].Kills_Player, Some(300));
+        assert_eq!(replies[0].Kills_Double, None);
+        assert_eq!(replies[0].Assists, None);
+
+        let fields: Vec<(&str, &str)> = anomalies.iter().map(|x| (x.field.as_str(), x.value.as_str())).collect();
+        assert_eq!(fields, vec![("Assists", "lots"), ("Kills_Double", "70000")]);
+        assert!(anomalies[1].reason.contains("expected u16"));
+    }
+
+    test required_fields_are_still_required() {
+        let items: Result<Vec<Item>, String> = decode(&ReturnDataType::Json, "[{ \"DeviceName\": null }]");
+        assert!(items.is_err());
+        let items: Result<Vec<Item>, String> = decode(&ReturnDataType::Json, "not json");
+        assert!(items.is_err());
+    }
+
+    #[cfg(not(feature = "xml"))]
+    test decode_xml_needs_feature() {
+        let replies: Result<Vec<GetMatchIdsByQueueReply>, String> = decode(&ReturnDataType::Xml, "<ArrayOfMatchIdsByQueue/>");
+        assert!(replies.is_err());
+    }
+}
+
+#[cfg(all(test, feature = "xml"))]
+test_suite! {
+    name test_decode_xml;
+    use super::*;
+    use crate::models::{GetMatchIdsByQueueReply, PlayerMatchDetails};
+
+    test decode_xml_array() {
+        let xml = "<?xml version=\"1.0\"?>\
+            <ArrayOfMatchIdsByQueue xmlns=\"http://schemas.datacontract.org/2004/07/SmiteAPI\" xmlns:i=\"http://www.w3.org/2001/XMLSchema-instance\">\
+            <MatchIdsByQueue><Active_Flag>n</Active_Flag><Match>956598608</Match><ret_msg i:nil=\"true\"/></MatchIdsByQueue>\
+            <MatchIdsByQueue><Active_Flag>y</Active_Flag><Match>956598609</Match><ret_msg i:nil=\"true\"></ret_msg></MatchIdsByQueue>\
+            </ArrayOfMatchIdsByQueue>";
+        let replies: Vec<GetMatchIdsByQueueReply> = decode(&ReturnDataType::Xml, xml).unwrap();
+
+        assert_eq!(replies.len(), 2);
+        assert_eq!(replies[0].Active_Flag, Some('n'));
+        assert_eq!(replies[1].Match, Some(String::from("956598609")));
+        assert!(replies.iter().all(|x| x.ret_msg.is_none()));
+
+        let replies: Vec<GetMatchIdsByQueueReply> = decode(&ReturnDataType::Xml, "<ArrayOfMatchIdsByQueue/>").unwrap();
+        assert!(replies.is_empty());
+    }
+
+    test decode_xml_nested_list() {
+        let xml = "<ArrayOfMatchDetails>\
+            <MatchDetails><Match>1234</Match><MergedPlayers>\
+            <MergedPlayer><merge_datetime>1/1/2019</merge_datetime><playerId>1</playerId><portalId>5</portalId></MergedPlayer>\
+            <MergedPlayer><merge_datetime>1/2/2019</merge_datetime><playerId>2</playerId><portalId>5</portalId></MergedPlayer>\
+            </MergedPlayers></MatchDetails>\
+            <MatchDetails><Match>1235</Match><MergedPlayers i:nil=\"true\"/></MatchDetails>\
+            </ArrayOfMatchDetails>";
+        let replies: Vec<PlayerMatchDetails> = decode(&ReturnDataType::Xml, xml).unwrap();
+
+        assert_eq!(replies[0].Match, Some(1234));
+        assert_eq!(replies[0].MergedPlayers.as_ref().unwrap()[1].playerId, "2");
+        assert!(replies[1].MergedPlayers.is_none());
+    }
+}
@@ -0,0 +1,397 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+#[cfg(test)]
+use galvanic_test::test_suite;
+
+// the most mismatched fields looked for in a single response
+const MAX_MISMATCHES: usize = 100;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Step {
+    Index(usize),
+    Key(String),
+}
+
+/*
+ * A field's name as it appears in a report, with array indexes left out so the same field of
+ * every record is counted together, e.g. MergedPlayers[].playerId
+ */
+pub(crate) fn field_name(steps: &[Step]) -> String {
+    let mut name = String::new();
+    for step in steps {
+        match step {
+            Step::Key(key) => {
+                if !name.is_empty() {
+                    name.push('.');
+                }
+                name.push_str(key);
+            }
+            // replies are arrays of records, so a leading index says nothing about the field
+            Step::Index(_) if name.is_empty() => {}
+            Step::Index(_) => name.push_str("[]"),
+        }
+    }
+    name
+}
+
+/*
+ * The field a deserialization error says is missing, going by the wording of serde's
+ * de::Error::missing_field, which serde_json and every derived Deserialize use
+ */
+pub(crate) fn missing_field(msg: &str) -> Option<&str> {
+    match msg.strip_prefix("missing field `") {
+        Some(rest) => rest.split('`').next(),
+        None => None,
+    }
+}
+
+pub(crate) fn error_steps(path: &serde_path_to_error::Path) -> Vec<Step> {
+    path.iter()
+        .filter_map(|x| match x {
+            serde_path_to_error::Segment::Seq { index } => Some(Step::Index(*index)),
+            serde_path_to_error::Segment::Map { key } => Some(Step::Key(key.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+fn ignored_steps(path: &serde_ignored::Path, steps: &mut Vec<Step>) {
+    match path {
+        serde_ignored::Path::Root => {}
+        serde_ignored::Path::Seq { parent, index } => {
+            ignored_steps(parent, steps);
+            steps.push(Step::Index(*index));
+        }
+        serde_ignored::Path::Map { parent, key } => {
+            ignored_steps(parent, steps);
+            steps.push(Step::Key(key.clone()));
+        }
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => ignored_steps(parent, steps),
+    }
+}
+
+pub(crate) fn value_at<'a>(value: &'a mut Value, steps: &[Step]) -> Option<&'a mut Value> {
+    let mut current = value;
+    for step in steps {
+        current = match (step, current) {
+            (Step::Key(key), Value::Object(fields)) => fields.get_mut(key)?,
+            (Step::Index(index), Value::Array(items)) => items.get_mut(*index)?,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+// takes the value at steps out of value, returning whether there was one
+fn remove(value: &mut Value, steps: &[Step]) -> bool {
+    let (last, parents) = match steps.split_last() {
+        Some(split) => split,
+        None => return false,
+    };
+
+    match (last, value_at(value, parents)) {
+        (Step::Key(key), Some(Value::Object(fields))) => fields.remove(key).is_some(),
+        (Step::Index(index), Some(Value::Array(items))) if *index < items.len() => {
+            items.remove(*index);
+            true
+        }
+        _ => false,
+    }
+}
+
+// the fields of the model that the raw reply didn't have
+fn find_missing(raw: &Value, model: &Value, steps: &mut Vec<Step>, missing: &mut Vec<String>) {
+    match (raw, model) {
+        (Value::Object(raw), Value::Object(model)) => {
+            for (key, model_value) in model {
+                steps.push(Step::Key(key.clone()));
+                match raw.get(key) {
+                    Some(raw_value) => find_missing(raw_value, model_value, steps, missing),
+                    None => missing.push(field_name(steps)),
+                }
+                steps.pop();
+            }
+        }
+        (Value::Array(raw), Value::Array(model)) => {
+            for (index, (raw_value, model_value)) in raw.iter().zip(model).enumerate() {
+                steps.push(Step::Index(index));
+                find_missing(raw_value, model_value, steps, missing);
+                steps.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+// how a single reply differs from the model it was parsed into
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ResponseDrift {
+    pub unknown_fields: Vec<String>,
+    pub missing_fields: Vec<String>,
+    // the field and why its value doesn't fit the model
+    pub type_mismatches: Vec<(String, String)>,
+}
+
+impl ResponseDrift {
+    pub fn is_empty(&self) -> bool {
+        self.unknown_fields.is_empty()
+            && self.missing_fields.is_empty()
+            && self.type_mismatches.is_empty()
+    }
+}
+
+/*
+ * Compares a JSON reply to T field by field
+ *
+ * A field that doesn't fit is reported and left out so the rest of the reply can still be
+ * compared. Only fails if the reply isn't JSON at all
+ */
+pub fn detect<T: DeserializeOwned + Serialize>(body: &str) -> Result<ResponseDrift, String> {
+    let mut raw: Value = serde_json::from_str(body).map_err(|msg| msg.to_string())?;
+    let mut drift = ResponseDrift::default();
+
+    for _ in 0..MAX_MISMATCHES {
+        let err = match serde_path_to_error::deserialize::<_, T>(&raw) {
+            Ok(_) => break,
+            Err(err) => err,
+        };
+        let steps = error_steps(err.path());
+        let msg = err.inner().to_string();
+
+        // a required field that is missing can't be left out, so nothing more can be compared
+        if let Some(field) = missing_field(&msg) {
+            let mut name = field_name(&steps);
+            if !name.is_empty() {
+                name.push('.');
+            }
+            name.push_str(field);
+            drift.missing_fields.push(name);
+            return Ok(drift);
+        }
+
+        drift.type_mismatches.push((field_name(&steps), msg));
+        if !remove(&mut raw, &steps) {
+            return Ok(drift);
+        }
+    }
+
+    let mut unknown_fields = Vec::new();
+    let model: T = match serde_ignored::deserialize(&raw, |path| {
+        let mut steps = Vec::new();
+        ignored_steps(&path, &mut steps);
+        unknown_fields.push(field_name(&steps));
+    }) {
+        Ok(model) => model,
+        // more fields were mismatched than were looked for
+        Err(_) => return Ok(drift),
+    };
+    drift.unknown_fields = unknown_fields;
+
+    let model = serde_json::to_value(&model).map_err(|msg| msg.to_string())?;
+    let mut missing = Vec::new();
+    find_missing(&raw, &model, &mut Vec::new(), &mut missing);
+    // mismatched fields were removed, but they weren't missing
+    missing.retain(|x| !drift.type_mismatches.iter().any(|(field, _)| field == x));
+    drift.missing_fields.extend(missing);
+
+    Ok(drift)
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct TypeMismatch {
+    pub count: usize,
+    // the most recent reason given
+    pub example: String,
+}
+
+// how the replies to one method differed from its model, counted per field
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct EndpointDrift {
+    pub responses: usize,
+    pub unknown_fields: BTreeMap<String, usize>,
+    pub missing_fields: BTreeMap<String, usize>,
+    pub type_mismatches: BTreeMap<String, TypeMismatch>,
+}
+
+impl EndpointDrift {
+    pub fn is_clean(&self) -> bool {
+        self.unknown_fields.is_empty()
+            && self.missing_fields.is_empty()
+            && self.type_mismatches.is_empty()
+    }
+
+    fn add(&mut self, drift: ResponseDrift) {
+        self.responses += 1;
+        for field in drift.unknown_fields {
+            *self.unknown_fields.entry(field).or_default() += 1;
+        }
+        for field in drift.missing_fields {
+            *self.missing_fields.entry(field).or_default() += 1;
+        }
+        for (field, msg) in drift.type_mismatches {
+            let mismatch = self.type_mismatches.entry(field).or_default();
+            mismatch.count += 1;
+            mismatch.example = msg;
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct DriftReport {
+    // keyed by method name
+    pub endpoints: BTreeMap<String, EndpointDrift>,
+}
+
+impl DriftReport {
+    pub fn is_clean(&self) -> bool {
+        self.endpoints.values().all(|x| x.is_clean())
+    }
+}
+
+/*
+ * Checks replies against their models as they are received, building up a report of how
+ * HiRez's responses have drifted from them
+ *
+ * Pass one to RequestMaker::set_drift_detector. Replies are parsed as usual either way
+ */
+#[derive(Default)]
+pub struct DriftDetector {
+    report: Mutex<DriftReport>,
+}
+
+impl DriftDetector {
+    pub fn new() -> DriftDetector {
+        DriftDetector::default()
+    }
+
+    pub fn check<T: DeserializeOwned + Serialize>(&self, method_name: &str, body: &str) {
+        // replies that aren't JSON are reported when they fail to parse
+        if let Ok(drift) = detect::<T>(body) {
+            let mut report = self.report.lock().unwrap();
+            report
+                .endpoints
+                .entry(String::from(method_name))
+                .or_default()
+                .add(drift);
+        }
+    }
+
+    pub fn report(&self) -> DriftReport {
+        self.report.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+test_suite! {
+    name test_drift;
+    use super::*;
+    use crate::endpoints::{Endpoint, GetMatchDetailsBatch};
+    use crate::models::{Item, PlayerMatchDetails};
+    use crate::request_maker::RequestMaker;
+    use crate::reqwest_wrapper::MockWrapper as ReqwestWrapper;
+    use crate::test_responses;
+
+    // the match details test response with Kills_Player overflowing its u16, a new field, and
+    // Assists removed, in the first record
+    fn drifted_match_details() -> String {
+        let mut records: Value = serde_json::from_str(test_responses::GET_MATCH_DETAILS).unwrap();
+        let first = records[0].as_object_mut().unwrap();
+        first.insert(String::from("Kills_Player"), Value::from(70000));
+        first.insert(String::from("Kills_Jungle_Boss"), Value::from(1));
+        first.remove("Assists");
+        records.to_string()
+    }
+
+    test matching_reply_has_no_drift() {
+        let drift = detect::<Vec<PlayerMatchDetails>>(test_responses::GET_MATCH_DETAILS).unwrap();
+        assert!(drift.is_empty());
+    }
+
+    test drift_is_reported_per_field() {
+        let drift = detect::<Vec<PlayerMatchDetails>>(&drifted_match_details()).unwrap();
+
+        assert_eq!(drift.unknown_fields, vec![String::from("Kills_Jungle_Boss")]);
+        assert_eq!(drift.missing_fields, vec![String::from("Assists")]);
+        assert_eq!(drift.type_mismatches.len(), 1);
+        assert_eq!(drift.type_mismatches[0].0, "Kills_Player");
+        assert!(drift.type_mismatches[0].1.contains("expected u16"));
+    }
+
+    test missing_required_field() {
+        let drift = detect::<Vec<Item>>("[{ \"ItemId\": 1 }]").unwrap();
+        assert_eq!(drift.missing_fields, vec![String::from("DeviceName")]);
+    }
+
+    // detect and decode_lenient rely on how serde words a missing field
+    test missing_field_wording() {
+        let err = serde_path_to_error::deserialize::<_, Vec<Item>>(&serde_json::json!([{ "ItemId": 1 }]))
+            .unwrap_err();
+        assert_eq!(missing_field(&err.inner().to_string()), Some("DeviceName"));
+        assert_eq!(missing_field("invalid type: string \"a\", expected u16"), None);
+    }
+
+    test detector_aggregates_by_endpoint() {
+        let detector = DriftDetector::new();
+        detector.check::<Vec<PlayerMatchDetails>>("getmatchdetailsbatch", &drifted_match_details());
+        detector.check::<Vec<PlayerMatchDetails>>("getmatchdetailsbatch", &drifted_match_details());
+        detector.check::<Vec<PlayerMatchDetails>>("getmatchdetailsbatch", "not json");
+
+        let report = detector.report();
+        let endpoint = &report.endpoints["getmatchdetailsbatch"];
+        assert_eq!(endpoint.responses, 2);
+        assert_eq!(endpoint.type_mismatches["Kills_Player"].count, 2);
+        assert_eq!(endpoint.missing_fields["Assists"], 2);
+        assert!(!report.is_clean());
+    }
+
+    // a third party endpoint whose response can't be serialized, so isn't checked
+    struct GetVersion;
+
+    #[derive(Deserialize)]
+    struct Version {
+        #[allow(dead_code)]
+        version: String,
+    }
+
+    impl Endpoint for GetVersion {
+        type Response = Version;
+
+        fn method_name(&self) -> String {
+            String::from("getversion")
+        }
+
+        fn path(&self) -> Result<String, String> {
+            Ok(String::new())
+        }
+    }
+
+    test endpoints_without_serialize_are_not_checked() {
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().times(1).returning(|_x| Ok(String::from("{ \"version\": \"1\", \"extra\": 1 }")));
+        let mut request_maker = RequestMaker::mock(reqwest);
+        request_maker.set_drift_detector(DriftDetector::new());
+
+        assert!(request_maker.execute(vec![GetVersion])[0].is_ok());
+        assert!(request_maker.drift_report().unwrap().endpoints.is_empty());
+    }
+
+    test request_maker_reports_drift_without_failing() {
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().times(1).returning(|_x| Ok(drifted_match_details()));
+        let mut request_maker = RequestMaker::mock(reqwest);
+        request_maker.set_drift_detector(DriftDetector::new());
+
+        let replies = request_maker.execute(vec![GetMatchDetailsBatch { match_ids: vec![String::from("956598608")] }]);
+        // the overflowing Kills_Player is left empty rather than failing the reply
+        assert_eq!(replies[0].as_ref().unwrap()[0].Kills_Player, None);
+
+        let report = request_maker.drift_report().unwrap();
+        assert!(report.endpoints["getmatchdetailsbatch"].unknown_fields.contains_key("Kills_Jungle_Boss"));
+    }
+}
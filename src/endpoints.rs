@@ -0,0 +1,414 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Timelike, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+use crate::drift::DriftDetector;
+use crate::hi_rez_constants::{DataConstants, Language, UrlConstants};
+use crate::models::{GetMatchIdsByQueueReply, God, Item, PlayerMatchDetails};
+
+#[cfg(test)]
+use galvanic_test::test_suite;
+
+const WINDOW_MINUTES: i64 = 10;
+
+/*
+ * A HiRez API method, along with the parameters of a single call to it
+ *
+ * Implement this for methods HiRust doesn't ship and pass them to RequestMaker::execute
+ */
+pub trait Endpoint: Send + 'static {
+    type Response: DeserializeOwned + Send + 'static;
+
+    // e.g. getgods, as it appears in the url and signature
+    fn method_name(&self) -> String;
+
+    // the parameters appended to the url after the timestamp, including the leading slash
+    fn path(&self) -> Result<String, String>;
+
+    /*
+     * Compares a JSON reply to Response when RequestMaker has a DriftDetector
+     *
+     * Finding drift needs Response to be Serialize, so this does nothing unless overridden with
+     * detector.check::<Self::Response>(method_name, body)
+     */
+    fn check_drift(_detector: &DriftDetector, _method_name: &str, _body: &str)
+    where
+        Self: Sized,
+    {
+    }
+}
+
+pub fn format_date(date: NaiveDate) -> String {
+    format!("{}{:02}{:02}", date.year(), date.month(), date.day(),)
+}
+
+fn construct_batch_match_id_string(match_ids: &[String]) -> String {
+    format!("/{}", match_ids.join(","))
+}
+
+#[derive(Debug, Clone)]
+pub struct GetGods {
+    pub language: Language,
+}
+
+impl Endpoint for GetGods {
+    type Response = Vec<God>;
+
+    fn method_name(&self) -> String {
+        UrlConstants::GetGods.val()
+    }
+
+    fn path(&self) -> Result<String, String> {
+        Ok(format!("/{}", self.language.val()))
+    }
+
+    fn check_drift(detector: &DriftDetector, method_name: &str, body: &str) {
+        detector.check::<Self::Response>(method_name, body);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GetItems {
+    pub language: Language,
+}
+
+impl Endpoint for GetItems {
+    type Response = Vec<Item>;
+
+    fn method_name(&self) -> String {
+        UrlConstants::GetItems.val()
+    }
+
+    fn path(&self) -> Result<String, String> {
+        Ok(format!("/{}", self.language.val()))
+    }
+
+    fn check_drift(detector: &DriftDetector, method_name: &str, body: &str) {
+        detector.check::<Self::Response>(method_name, body);
+    }
+}
+
+// HiRez accepts at most 10 match ids per call
+#[derive(Debug, Clone)]
+pub struct GetMatchDetailsBatch {
+    pub match_ids: Vec<String>,
+}
+
+impl Endpoint for GetMatchDetailsBatch {
+    type Response = Vec<PlayerMatchDetails>;
+
+    fn method_name(&self) -> String {
+        UrlConstants::GetMatchDetailsBatch.val()
+    }
+
+    fn path(&self) -> Result<String, String> {
+        if self.match_ids.is_empty() {
+            return Err(String::from("No match ids specified"));
+        } else if self.match_ids.len() > 10 {
+            return Err(String::from("At most 10 match ids may be requested at once"));
+        }
+        Ok(construct_batch_match_id_string(&self.match_ids))
+    }
+
+    fn check_drift(detector: &DriftDetector, method_name: &str, body: &str) {
+        detector.check::<Self::Response>(method_name, body);
+    }
+}
+
+// an hour of the day, 0 to 23
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(try_from = "u8", into = "u8")]
+pub struct Hour(u8);
+
+impl Hour {
+    pub fn new(hour: u8) -> Option<Hour> {
+        match hour {
+            0..=23 => Some(Hour(hour)),
+            _ => None,
+        }
+    }
+
+    pub fn get(self) -> u8 {
+        self.0
+    }
+
+    pub fn all() -> impl Iterator<Item = Hour> {
+        (0..24).map(Hour)
+    }
+}
+
+impl TryFrom<u8> for Hour {
+    type Error = String;
+
+    fn try_from(hour: u8) -> Result<Hour, String> {
+        Hour::new(hour).ok_or_else(|| String::from("Invalid hour specified"))
+    }
+}
+
+impl From<Hour> for u8 {
+    fn from(hour: Hour) -> u8 {
+        hour.0
+    }
+}
+
+// the ten minutes of an hour starting at :00, :10, ... :50
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(try_from = "u8", into = "u8")]
+pub enum MinuteSlot {
+    Zero,
+    Ten,
+    Twenty,
+    Thirty,
+    Forty,
+    Fifty,
+}
+
+impl MinuteSlot {
+    pub const ALL: [MinuteSlot; 6] = [
+        MinuteSlot::Zero,
+        MinuteSlot::Ten,
+        MinuteSlot::Twenty,
+        MinuteSlot::Thirty,
+        MinuteSlot::Forty,
+        MinuteSlot::Fifty,
+    ];
+
+    // None unless minute is a multiple of 10 below 60
+    pub fn new(minute: u8) -> Option<MinuteSlot> {
+        match minute {
+            0 => Some(MinuteSlot::Zero),
+            10 => Some(MinuteSlot::Ten),
+            20 => Some(MinuteSlot::Twenty),
+            30 => Some(MinuteSlot::Thirty),
+            40 => Some(MinuteSlot::Forty),
+            50 => Some(MinuteSlot::Fifty),
+            _ => None,
+        }
+    }
+
+    // the slot minute falls in
+    pub fn containing(minute: u8) -> Option<MinuteSlot> {
+        MinuteSlot::new(minute / 10 * 10)
+    }
+
+    pub fn minute(self) -> u8 {
+        match self {
+            MinuteSlot::Zero => 0,
+            MinuteSlot::Ten => 10,
+            MinuteSlot::Twenty => 20,
+            MinuteSlot::Thirty => 30,
+            MinuteSlot::Forty => 40,
+            MinuteSlot::Fifty => 50,
+        }
+    }
+}
+
+impl TryFrom<u8> for MinuteSlot {
+    type Error = String;
+
+    fn try_from(minute: u8) -> Result<MinuteSlot, String> {
+        MinuteSlot::new(minute).ok_or_else(|| String::from("Invalid minute specified"))
+    }
+}
+
+impl From<MinuteSlot> for u8 {
+    fn from(slot: MinuteSlot) -> u8 {
+        slot.minute()
+    }
+}
+
+/*
+ * The part of a day getmatchidsbyqueue returns matches for
+ */
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum TimeWindow {
+    WholeDay,
+    Hour(Hour),
+    TenMinutes(Hour, MinuteSlot),
+}
+
+impl TimeWindow {
+    pub fn hour(hour: u8) -> Result<TimeWindow, String> {
+        Ok(TimeWindow::Hour(Hour::try_from(hour)?))
+    }
+
+    pub fn ten_minutes(hour: u8, minute: u8) -> Result<TimeWindow, String> {
+        Ok(TimeWindow::TenMinutes(
+            Hour::try_from(hour)?,
+            MinuteSlot::try_from(minute)?,
+        ))
+    }
+
+    // the window as HiRez expects it in the url
+    pub fn val(&self) -> String {
+        match *self {
+            TimeWindow::WholeDay => String::from("-1"),
+            TimeWindow::Hour(hour) => hour.get().to_string(),
+            TimeWindow::TenMinutes(hour, slot) => format!("{},{:02}", hour.get(), slot.minute()),
+        }
+    }
+
+    // the next smaller windows that together cover this one
+    pub fn subdivide(&self) -> Vec<TimeWindow> {
+        match *self {
+            TimeWindow::WholeDay => Hour::all().map(TimeWindow::Hour).collect(),
+            TimeWindow::Hour(hour) => MinuteSlot::ALL
+                .iter()
+                .map(|x| TimeWindow::TenMinutes(hour, *x))
+                .collect(),
+            TimeWindow::TenMinutes(_, _) => Vec::new(),
+        }
+    }
+
+    /*
+     * The fewest windows that together cover start up to end, widened to ten minute boundaries
+     */
+    pub fn from_range(start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<(NaiveDate, TimeWindow)> {
+        let window = Duration::minutes(WINDOW_MINUTES);
+        let mut current = start
+            .with_minute(start.minute() / 10 * 10)
+            .and_then(|x| x.with_second(0))
+            .and_then(|x| x.with_nanosecond(0))
+            .unwrap();
+        let mut windows = Vec::new();
+
+        while current < end {
+            let whole_day = current.hour() == 0 && current.minute() == 0;
+            let whole_hour = current.minute() == 0;
+            // always valid, as they come from a time of day
+            let hour = Hour(current.hour() as u8);
+            let slot = MinuteSlot::containing(current.minute() as u8).unwrap();
+
+            if whole_day && current + Duration::days(1) <= end {
+                windows.push((current.date_naive(), TimeWindow::WholeDay));
+                current += Duration::days(1);
+            } else if whole_hour && current + Duration::hours(1) <= end {
+                windows.push((current.date_naive(), TimeWindow::Hour(hour)));
+                current += Duration::hours(1);
+            } else {
+                windows.push((current.date_naive(), TimeWindow::TenMinutes(hour, slot)));
+                current += window;
+            }
+        }
+
+        windows
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GetMatchIdsByQueueRequest {
+    pub queue_id: DataConstants,
+    pub date: NaiveDate,
+    pub window: TimeWindow,
+}
+
+impl GetMatchIdsByQueueRequest {
+    /*
+     * The requests needed to retrieve every match in queue_id from start up to end
+     */
+    pub fn for_range(
+        queue_id: DataConstants,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Vec<GetMatchIdsByQueueRequest> {
+        TimeWindow::from_range(start, end)
+            .into_iter()
+            .map(|(date, window)| GetMatchIdsByQueueRequest {
+                queue_id,
+                date,
+                window,
+            })
+            .collect()
+    }
+}
+
+impl Endpoint for GetMatchIdsByQueueRequest {
+    type Response = Vec<GetMatchIdsByQueueReply>;
+
+    fn method_name(&self) -> String {
+        UrlConstants::GetMatchIdsByQueue.val()
+    }
+
+    fn path(&self) -> Result<String, String> {
+        Ok(format!(
+            "/{}/{}/{}",
+            self.queue_id.val(),
+            format_date(self.date),
+            self.window.val()
+        ))
+    }
+
+    fn check_drift(detector: &DriftDetector, method_name: &str, body: &str) {
+        detector.check::<Self::Response>(method_name, body);
+    }
+}
+
+#[cfg(test)]
+test_suite! {
+    name test_endpoints;
+    use super::*;
+    use chrono::TimeZone;
+
+    test format_date_correct() {
+        let expected_string = String::from("20190810");
+        let date = NaiveDate::from_ymd_opt(2019, 8, 10).unwrap();
+        let generated_string: String = format_date(date);
+        assert_eq!(generated_string, expected_string);
+    }
+
+    test construct_batch_match_id_string_correct() {
+        let match_ids: Vec<String> = vec!["1", "2", "3"].into_iter().map(|x| x.to_string()).collect();
+        let expected_string = String::from("/1,2,3");
+        assert_eq!(expected_string, construct_batch_match_id_string(&match_ids));
+    }
+
+    test match_ids_by_queue_path() {
+        let request = GetMatchIdsByQueueRequest {
+            queue_id: DataConstants::RankedConquest,
+            date: NaiveDate::from_ymd_opt(2019, 8, 5).unwrap(),
+            window: TimeWindow::ten_minutes(3, 0).unwrap(),
+        };
+        assert_eq!(request.method_name(), "getmatchidsbyqueue");
+        assert_eq!(request.path(), Ok(String::from("/451/20190805/3,00")));
+    }
+
+    test invalid_time_windows() {
+        assert_eq!(TimeWindow::hour(24), Err(String::from("Invalid hour specified")));
+        assert_eq!(TimeWindow::ten_minutes(3, 15), Err(String::from("Invalid minute specified")));
+        assert_eq!(TimeWindow::ten_minutes(3, 60), Err(String::from("Invalid minute specified")));
+        assert_eq!(TimeWindow::hour(12).unwrap().val(), "12");
+        assert_eq!(TimeWindow::ten_minutes(3, 50).unwrap().val(), "3,50");
+        assert_eq!(TimeWindow::WholeDay.val(), "-1");
+        assert_eq!(Hour::new(24), None);
+        assert_eq!(MinuteSlot::new(23), None);
+        assert!(serde_json::from_str::<TimeWindow>("{ \"TenMinutes\": [3, 15] }").is_err());
+    }
+
+    test minimal_windows_for_range() {
+        let start = Utc.with_ymd_and_hms(2019, 8, 4, 22, 45, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2019, 8, 6, 1, 20, 0).unwrap();
+        let windows = TimeWindow::from_range(start, end);
+        let day = |x| NaiveDate::from_ymd_opt(2019, 8, x).unwrap();
+
+        assert_eq!(
+            windows,
+            vec![
+                (day(4), TimeWindow::ten_minutes(22, 40).unwrap()),
+                (day(4), TimeWindow::ten_minutes(22, 50).unwrap()),
+                (day(4), TimeWindow::hour(23).unwrap()),
+                (day(5), TimeWindow::WholeDay),
+                (day(6), TimeWindow::hour(0).unwrap()),
+                (day(6), TimeWindow::ten_minutes(1, 0).unwrap()),
+                (day(6), TimeWindow::ten_minutes(1, 10).unwrap()),
+            ]
+        );
+        assert!(TimeWindow::from_range(end, start).is_empty());
+    }
+
+    test match_details_batch_limits() {
+        let too_many = GetMatchDetailsBatch { match_ids: vec![String::from("1"); 11] };
+        assert!(too_many.path().is_err());
+        assert!(GetMatchDetailsBatch { match_ids: Vec::new() }.path().is_err());
+    }
+}
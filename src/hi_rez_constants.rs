@@ -1,7 +1,16 @@
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+
+#[cfg(test)]
+use galvanic_test::test_suite;
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum UrlConstants {
     UrlBase,
     DataLimitCheck,
     CreateSession,
+    TestSession,
     GetMatchDetails,
     GetMatchDetailsBatch,
     GetMatchIdsByQueue,
@@ -15,6 +24,7 @@ impl UrlConstants {
             UrlConstants::UrlBase => String::from("https://api.smitegame.com/smiteapi.svc"),
             UrlConstants::DataLimitCheck => String::from("getdataused"),
             UrlConstants::CreateSession => String::from("createsession"),
+            UrlConstants::TestSession => String::from("testsession"),
             UrlConstants::GetMatchDetails => String::from("getmatchdetails"),
             UrlConstants::GetMatchDetailsBatch => String::from("getmatchdetailsbatch"),
             UrlConstants::GetMatchIdsByQueue => String::from("getmatchidsbyqueue"),
@@ -24,6 +34,7 @@ impl UrlConstants {
     }
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub enum ReturnDataType {
     Json,
     Xml,
@@ -38,36 +49,259 @@ impl ReturnDataType {
     }
 }
 
-#[derive(Debug, Clone)]
+// the queues matches are played in, identified by HiRez's queue id
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum DataConstants {
+    Conquest,
+    Arena,
+    Joust,
+    Assault,
+    Clash,
+    Siege,
+    Motd,
+    Slash,
     RankedConquest,
     RankedJoust,
     RankedDuel,
+    RankedConquestController,
+    RankedJoustController,
+    RankedDuelController,
+    CustomConquest,
+    CustomArena,
+    CustomJoust,
+    CustomAssault,
+    CustomClash,
+    CustomSiege,
+    CustomSlash,
+    ArenaTutorial,
+    ConquestTutorial,
+    BasicTutorial,
+    JunglePractice,
 }
 
 impl DataConstants {
+    // the queue id as used in urls
     pub fn val(&self) -> String {
+        self.id().to_string()
+    }
+
+    pub fn id(&self) -> u16 {
+        match *self {
+            DataConstants::Conquest => 426,
+            DataConstants::Arena => 435,
+            DataConstants::Joust => 448,
+            DataConstants::Assault => 445,
+            DataConstants::Clash => 466,
+            DataConstants::Siege => 459,
+            DataConstants::Motd => 434,
+            DataConstants::Slash => 10189,
+            DataConstants::RankedConquest => 451,
+            DataConstants::RankedJoust => 450,
+            DataConstants::RankedDuel => 440,
+            DataConstants::RankedConquestController => 504,
+            DataConstants::RankedJoustController => 503,
+            DataConstants::RankedDuelController => 502,
+            DataConstants::CustomConquest => 429,
+            DataConstants::CustomArena => 438,
+            DataConstants::CustomJoust => 441,
+            DataConstants::CustomAssault => 446,
+            DataConstants::CustomClash => 467,
+            DataConstants::CustomSiege => 460,
+            DataConstants::CustomSlash => 10190,
+            DataConstants::ArenaTutorial => 462,
+            DataConstants::ConquestTutorial => 463,
+            DataConstants::BasicTutorial => 436,
+            DataConstants::JunglePractice => 444,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            DataConstants::Conquest => "Conquest",
+            DataConstants::Arena => "Arena",
+            DataConstants::Joust => "Joust",
+            DataConstants::Assault => "Assault",
+            DataConstants::Clash => "Clash",
+            DataConstants::Siege => "Siege",
+            DataConstants::Motd => "Match of the Day",
+            DataConstants::Slash => "Slash",
+            DataConstants::RankedConquest => "Ranked Conquest",
+            DataConstants::RankedJoust => "Ranked Joust",
+            DataConstants::RankedDuel => "Ranked Duel",
+            DataConstants::RankedConquestController => "Ranked Conquest (Controller)",
+            DataConstants::RankedJoustController => "Ranked Joust (Controller)",
+            DataConstants::RankedDuelController => "Ranked Duel (Controller)",
+            DataConstants::CustomConquest => "Custom Conquest",
+            DataConstants::CustomArena => "Custom Arena",
+            DataConstants::CustomJoust => "Custom Joust",
+            DataConstants::CustomAssault => "Custom Assault",
+            DataConstants::CustomClash => "Custom Clash",
+            DataConstants::CustomSiege => "Custom Siege",
+            DataConstants::CustomSlash => "Custom Slash",
+            DataConstants::ArenaTutorial => "Arena Tutorial",
+            DataConstants::ConquestTutorial => "Conquest Tutorial",
+            DataConstants::BasicTutorial => "Basic Tutorial",
+            DataConstants::JunglePractice => "Jungle Practice",
+        }
+    }
+
+    // the number of players on each team
+    pub fn team_size(&self) -> u8 {
         match *self {
-            DataConstants::RankedConquest => String::from("451"),
-            DataConstants::RankedJoust => String::from("450"),
-            DataConstants::RankedDuel => String::from("440"),
+            DataConstants::RankedDuel
+            | DataConstants::RankedDuelController
+            | DataConstants::BasicTutorial
+            | DataConstants::JunglePractice => 1,
+            DataConstants::Joust
+            | DataConstants::RankedJoust
+            | DataConstants::RankedJoustController
+            | DataConstants::CustomJoust => 3,
+            DataConstants::Siege | DataConstants::CustomSiege => 4,
+            _ => 5,
         }
     }
 
+    pub fn is_ranked(&self) -> bool {
+        matches!(
+            *self,
+            DataConstants::RankedConquest
+                | DataConstants::RankedJoust
+                | DataConstants::RankedDuel
+                | DataConstants::RankedConquestController
+                | DataConstants::RankedJoustController
+                | DataConstants::RankedDuelController
+        )
+    }
+
+    pub fn is_custom(&self) -> bool {
+        matches!(
+            *self,
+            DataConstants::CustomConquest
+                | DataConstants::CustomArena
+                | DataConstants::CustomJoust
+                | DataConstants::CustomAssault
+                | DataConstants::CustomClash
+                | DataConstants::CustomSiege
+                | DataConstants::CustomSlash
+        )
+    }
+
+    pub fn all() -> Vec<DataConstants> {
+        vec![
+            DataConstants::Conquest,
+            DataConstants::Arena,
+            DataConstants::Joust,
+            DataConstants::Assault,
+            DataConstants::Clash,
+            DataConstants::Siege,
+            DataConstants::Motd,
+            DataConstants::Slash,
+            DataConstants::RankedConquest,
+            DataConstants::RankedJoust,
+            DataConstants::RankedDuel,
+            DataConstants::RankedConquestController,
+            DataConstants::RankedJoustController,
+            DataConstants::RankedDuelController,
+            DataConstants::CustomConquest,
+            DataConstants::CustomArena,
+            DataConstants::CustomJoust,
+            DataConstants::CustomAssault,
+            DataConstants::CustomClash,
+            DataConstants::CustomSiege,
+            DataConstants::CustomSlash,
+            DataConstants::ArenaTutorial,
+            DataConstants::ConquestTutorial,
+            DataConstants::BasicTutorial,
+            DataConstants::JunglePractice,
+        ]
+    }
+
+    pub fn from_id(id: u16) -> Result<DataConstants, String> {
+        DataConstants::all()
+            .into_iter()
+            .find(|x| x.id() == id)
+            .ok_or_else(|| format!("DataConstant match not found for id: {}", id))
+    }
+
+    /*
+     * Accepts a queue id, a variant name such as RankedConquest or a display name such as
+     * "Ranked Conquest", ignoring case
+     */
     pub fn from_str(constant_str: String) -> Result<DataConstants, String> {
-        if &constant_str == "RankedConquest" {
-            return Ok(DataConstants::RankedConquest);
-        } else if &constant_str == "RankedJoust" {
-            return Ok(DataConstants::RankedJoust);
-        } else if &constant_str == "RankedDuel" {
-            return Ok(DataConstants::RankedDuel);
-        } else {
-            return Err(format!(
-                "DataConstant match not found for: {}",
-                constant_str
-            ));
+        let trimmed = constant_str.trim();
+        if let Ok(id) = trimmed.parse::<u16>() {
+            return DataConstants::from_id(id);
+        }
+
+        DataConstants::all()
+            .into_iter()
+            .find(|x| {
+                format!("{:?}", x).eq_ignore_ascii_case(trimmed)
+                    || x.name().eq_ignore_ascii_case(trimmed)
+            })
+            .ok_or_else(|| format!("DataConstant match not found for: {}", constant_str))
+    }
+}
+
+impl TryFrom<u16> for DataConstants {
+    type Error = String;
+
+    fn try_from(id: u16) -> Result<DataConstants, String> {
+        DataConstants::from_id(id)
+    }
+}
+
+impl fmt::Display for DataConstants {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// the language codes HiRez uses for localized text
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Language {
+    English,
+    German,
+    French,
+    Chinese,
+    Spanish,
+    SpanishLatAm,
+    Portuguese,
+    Russian,
+    Polish,
+    Turkish,
+}
+
+impl Language {
+    pub fn val(&self) -> String {
+        match *self {
+            Language::English => String::from("1"),
+            Language::German => String::from("2"),
+            Language::French => String::from("3"),
+            Language::Chinese => String::from("5"),
+            Language::Spanish => String::from("7"),
+            Language::SpanishLatAm => String::from("9"),
+            Language::Portuguese => String::from("10"),
+            Language::Russian => String::from("11"),
+            Language::Polish => String::from("12"),
+            Language::Turkish => String::from("13"),
         }
     }
+
+    pub fn all() -> Vec<Language> {
+        vec![
+            Language::English,
+            Language::German,
+            Language::French,
+            Language::Chinese,
+            Language::Spanish,
+            Language::SpanishLatAm,
+            Language::Portuguese,
+            Language::Russian,
+            Language::Polish,
+            Language::Turkish,
+        ]
+    }
 }
 
 pub enum LimitConstants {
@@ -89,3 +323,33 @@ impl LimitConstants {
         }
     }
 }
+
+#[cfg(test)]
+test_suite! {
+    name test_hi_rez_constants;
+    use super::*;
+    use std::collections::HashSet;
+
+    test queue_ids_are_unique() {
+        let ids: HashSet<u16> = DataConstants::all().iter().map(|x| x.id()).collect();
+        assert_eq!(ids.len(), DataConstants::all().len());
+    }
+
+    test queue_parsing() {
+        assert_eq!(DataConstants::try_from(451), Ok(DataConstants::RankedConquest));
+        assert_eq!(DataConstants::from_str(String::from("RankedJoust")), Ok(DataConstants::RankedJoust));
+        assert_eq!(DataConstants::from_str(String::from("match of the day")), Ok(DataConstants::Motd));
+        assert_eq!(DataConstants::from_str(String::from("10189")), Ok(DataConstants::Slash));
+        assert!(DataConstants::try_from(1).is_err());
+        assert!(DataConstants::from_str(String::from("Ranked")).is_err());
+    }
+
+    test queue_details() {
+        assert_eq!(DataConstants::RankedDuelController.val(), "502");
+        assert_eq!(DataConstants::Siege.team_size(), 4);
+        assert!(DataConstants::RankedJoustController.is_ranked());
+        assert!(!DataConstants::Joust.is_ranked());
+        assert!(DataConstants::CustomSlash.is_custom());
+        assert_eq!(DataConstants::RankedConquest.to_string(), "Ranked Conquest");
+    }
+}
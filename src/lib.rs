@@ -1,8 +1,26 @@
+pub mod auth;
+pub mod bulk;
 pub mod hi_rez_constants;
 pub mod models;
+pub mod pending;
 pub mod request_maker;
 pub mod session_manager;
+pub mod session_store;
+pub mod sink;
 pub mod url_builder;
 pub mod reqwest_wrapper;
 pub mod test_responses;
 pub mod api;
+pub mod archive;
+pub mod clock;
+pub mod crawler;
+pub mod credential_pool;
+pub mod decode;
+pub mod drift;
+pub mod endpoints;
+pub mod matches;
+pub mod typed;
+#[cfg(unix)]
+pub mod broker;
+#[cfg(feature = "columnar")]
+pub mod columnar;
@@ -0,0 +1,255 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::hi_rez_constants::DataConstants;
+use crate::models::PlayerMatchDetails;
+
+#[cfg(test)]
+use galvanic_test::test_suite;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Ban {
+    pub god_id: u16,
+    pub god_name: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Team {
+    // 1 or 2, as in PlayerMatchDetails::TaskForce
+    pub task_force: u8,
+    pub score: Option<u64>,
+    pub won: bool,
+    // the rows as HiRez returned them, so each still repeats the match and team fields
+    pub players: Vec<PlayerMatchDetails>,
+}
+
+/*
+ * A match put back together from the one PlayerMatchDetails per player HiRez returns
+ *
+ * Fields every row repeats are kept once, taken from the first row. The players are left
+ * unchanged, so they still carry those fields too
+ */
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Match {
+    pub match_id: u32,
+    pub queue_id: Option<u16>,
+    pub map: Option<String>,
+    pub entry_datetime: Option<String>,
+    pub duration_seconds: Option<u64>,
+    pub first_ban_side: Option<String>,
+    pub winning_task_force: Option<u8>,
+    // in the order they were made, without unused ban slots
+    pub bans: Vec<Ban>,
+    // ordered by task force
+    pub teams: Vec<Team>,
+}
+
+fn bans(row: &PlayerMatchDetails) -> Vec<Ban> {
+    let slots = vec![
+        (row.Ban1Id, &row.Ban1),
+        (row.Ban2Id, &row.Ban2),
+        (row.Ban3Id, &row.Ban3),
+        (row.Ban4Id, &row.Ban4),
+        (row.Ban5Id, &row.Ban5),
+        (row.Ban6Id, &row.Ban6),
+        (row.Ban7Id, &row.Ban7),
+        (row.Ban8Id, &row.Ban8),
+        (row.Ban9Id, &row.Ban9),
+        (row.Ban10Id, &row.Ban10),
+    ];
+
+    // HiRez fills unused slots with an id of 0
+    slots
+        .into_iter()
+        .filter_map(|(god_id, god_name)| match god_id {
+            Some(god_id) if god_id != 0 => Some(Ban {
+                god_id,
+                god_name: god_name.clone().filter(|x| !x.is_empty()),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+impl Match {
+    /*
+     * Builds a match from every row returned for it
+     */
+    pub fn from_players(players: Vec<PlayerMatchDetails>) -> Result<Match, String> {
+        let first = match players.first() {
+            Some(first) => first,
+            None => return Err(String::from("No players to build a match from")),
+        };
+        let match_id = first
+            .Match
+            .ok_or_else(|| String::from("Player match details are missing a match id"))?;
+
+        let mut match_ = Match {
+            match_id,
+            queue_id: first.match_queue_id,
+            map: first.Map_Game.clone(),
+            entry_datetime: first.Entry_Datetime.clone(),
+            duration_seconds: first.Match_Duration,
+            first_ban_side: first.First_Ban_Side.clone(),
+            winning_task_force: first.Winning_TaskForce,
+            bans: bans(first),
+            teams: Vec::new(),
+        };
+
+        let mut teams: BTreeMap<u8, Vec<PlayerMatchDetails>> = BTreeMap::new();
+        for player in players {
+            if player.Match != Some(match_id) {
+                return Err(format!(
+                    "Player match details for {:?} can't be part of match {}",
+                    player.Match, match_id
+                ));
+            }
+            let task_force = player
+                .TaskForce
+                .ok_or_else(|| format!("A player in match {} has no task force", match_id))?;
+            teams.entry(task_force).or_default().push(player);
+        }
+
+        match_.teams = teams
+            .into_iter()
+            .map(|(task_force, players)| Team {
+                task_force,
+                score: match task_force {
+                    1 => players[0].Team1Score,
+                    _ => players[0].Team2Score,
+                },
+                won: match_.winning_task_force == Some(task_force),
+                players,
+            })
+            .collect();
+
+        Ok(match_)
+    }
+
+    /*
+     * Groups rows by match id, in the order each match first appears
+     */
+    pub fn group(rows: Vec<PlayerMatchDetails>) -> Vec<Result<Match, String>> {
+        let mut order: Vec<u32> = Vec::new();
+        let mut by_match: BTreeMap<u32, Vec<PlayerMatchDetails>> = BTreeMap::new();
+        let mut matches = Vec::new();
+
+        for row in rows {
+            match row.Match {
+                Some(match_id) => {
+                    if !by_match.contains_key(&match_id) {
+                        order.push(match_id);
+                    }
+                    by_match.entry(match_id).or_default().push(row);
+                }
+                None => matches.push(Err(match row.ret_msg {
+                    Some(msg) => format!("Player match details are missing a match id: {}", msg),
+                    None => String::from("Player match details are missing a match id"),
+                })),
+            }
+        }
+
+        for match_id in order {
+            matches.push(Match::from_players(by_match.remove(&match_id).unwrap()));
+        }
+        matches
+    }
+
+    // None if the queue id is missing or one HiRust doesn't know
+    pub fn queue(&self) -> Option<DataConstants> {
+        self.queue_id.and_then(|x| DataConstants::from_id(x).ok())
+    }
+
+    pub fn winner(&self) -> Option<&Team> {
+        self.teams.iter().find(|x| x.won)
+    }
+
+    pub fn players(&self) -> impl Iterator<Item = &PlayerMatchDetails> {
+        self.teams.iter().flat_map(|x| x.players.iter())
+    }
+
+    /*
+     * Checks the match has two teams, each with as many players as its queue puts on a team
+     */
+    pub fn validate(&self) -> Result<(), String> {
+        let queue = self.queue().ok_or_else(|| {
+            format!("Match {} is in unknown queue {:?}", self.match_id, self.queue_id)
+        })?;
+        if self.teams.len() != 2 {
+            return Err(format!(
+                "Match {} has {} teams instead of 2",
+                self.match_id,
+                self.teams.len()
+            ));
+        }
+        for team in &self.teams {
+            if team.players.len() != queue.team_size() as usize {
+                return Err(format!(
+                    "Team {} of match {} has {} players, but {} has {} per team",
+                    team.task_force,
+                    self.match_id,
+                    team.players.len(),
+                    queue.name(),
+                    queue.team_size()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+test_suite! {
+    name test_matches;
+    use super::*;
+    use crate::test_responses;
+
+    fn rows() -> Vec<PlayerMatchDetails> {
+        serde_json::from_str(test_responses::GET_MATCH_DETAILS).unwrap()
+    }
+
+    test group_into_teams() {
+        let matches = Match::group(rows());
+        assert_eq!(matches.len(), 1);
+        let match_ = matches[0].as_ref().unwrap();
+
+        assert_eq!(match_.match_id, 956598608);
+        assert_eq!(match_.queue(), Some(DataConstants::RankedConquest));
+        assert_eq!(match_.teams.iter().map(|x| x.task_force).collect::<Vec<u8>>(), vec![1, 2]);
+        assert_eq!(match_.winner().unwrap().task_force, 1);
+        assert_eq!(match_.players().count(), 10);
+        assert_eq!(match_.bans.len(), 10);
+        assert_eq!(match_.bans[0], Ban { god_id: 2034, god_name: Some(String::from("Ao Kuang")) });
+        assert_eq!(match_.bans[9].god_id, 1993);
+        assert!(match_.validate().is_ok());
+
+        // the rows are kept as returned
+        let player = match_.players().next().unwrap();
+        assert_eq!(player.Match, Some(956598608));
+        assert!(player.GodId.is_some());
+        assert_eq!(match_.teams[0].score, rows()[0].Team1Score);
+    }
+
+    test validate_team_size() {
+        let mut rows = rows();
+        rows.pop();
+        let match_ = Match::from_players(rows.clone()).unwrap();
+        assert!(match_.validate().unwrap_err().contains("has 4 players"));
+
+        // a joust match only has 3 players per team
+        for row in rows.iter_mut() {
+            row.match_queue_id = Some(DataConstants::RankedJoust.id());
+        }
+        assert!(Match::from_players(rows).unwrap().validate().is_err());
+    }
+
+    test rows_from_other_matches_are_rejected() {
+        let mut rows = rows();
+        rows[3].Match = Some(1);
+        assert!(Match::from_players(rows.clone()).is_err());
+
+        let matches = Match::group(rows);
+        assert_eq!(matches.len(), 2);
+        assert!(matches[0].as_ref().unwrap().validate().is_err());
+    }
+}
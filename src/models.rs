@@ -1,4 +1,7 @@
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+use crate::hi_rez_constants::DataConstants;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct CreateSessionReply {
@@ -26,13 +29,13 @@ pub struct MergedPlayer {
 #[allow(non_snake_case)]
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct PlayerMatchDetails {
-    pub Account_Level: Option<u8>,
+    pub Account_Level: Option<u16>,
     pub ActiveId1: Option<u16>,
     pub ActiveId2: Option<u16>,
     pub ActiveId3: Option<u16>,
     pub ActiveId4: Option<u16>,
     pub ActivePlayerId: Option<String>,
-    pub Assists: Option<u8>,
+    pub Assists: Option<u16>,
     pub Ban1: Option<String>,
     pub Ban10: Option<String>,
     pub Ban10Id: Option<u16>,
@@ -53,7 +56,7 @@ pub struct PlayerMatchDetails {
     pub Ban8Id: Option<u16>,
     pub Ban9: Option<String>,
     pub Ban9Id: Option<u16>,
-    pub Camps_Cleared: Option<u8>,
+    pub Camps_Cleared: Option<u16>,
     pub Conquest_Losses: Option<u16>,
     pub Conquest_Points: Option<u16>,
     pub Conquest_Tier: Option<u8>,
@@ -67,14 +70,14 @@ pub struct PlayerMatchDetails {
     pub Damage_Taken: Option<u32>,
     pub Damage_Taken_Magical: Option<u32>,
     pub Damage_Taken_Physical: Option<u32>,
-    pub Deaths: Option<u8>,
+    pub Deaths: Option<u16>,
     pub Distance_Traveled: Option<u32>,
     pub Duel_Losses: Option<u16>,
     pub Duel_Points: Option<u16>,
     pub Duel_Tier: Option<u8>,
     pub Duel_Wins: Option<u16>,
     pub Entry_Datetime: Option<String>,
-    pub Final_Match_Level: Option<u8>,
+    pub Final_Match_Level: Option<u16>,
     pub First_Ban_Side: Option<String>,
     pub GodId: Option<u16>,
     pub Gold_Earned: Option<u32>,
@@ -102,28 +105,29 @@ pub struct PlayerMatchDetails {
     pub Joust_Points: Option<u16>,
     pub Joust_Tier: Option<u8>,
     pub Joust_Wins: Option<u16>,
-    pub Killing_Spree: Option<u8>,
-    pub Kills_Bot: Option<u16>,
-    pub Kills_Double: Option<u8>,
-    pub Kills_Fire_Giant: Option<u8>,
-    pub Kills_First_Blood: Option<u8>,
-    pub Kills_Gold_Fury: Option<u8>,
-    pub Kills_Penta: Option<u8>,
-    pub Kills_Phoenix: Option<u8>,
-    pub Kills_Player: Option<u8>,
-    pub Kills_Quadra: Option<u8>,
-    pub Kills_Siege_Juggernaut: Option<u8>,
-    pub Kills_Single: Option<u8>,
-    pub Kills_Triple: Option<u8>,
-    pub Kills_Wild_Juggernaut: Option<u8>,
+    pub Killing_Spree: Option<u16>,
+    pub Kills_Bot: Option<u32>,
+    pub Kills_Double: Option<u16>,
+    pub Kills_Fire_Giant: Option<u16>,
+    pub Kills_First_Blood: Option<u16>,
+    pub Kills_Gold_Fury: Option<u16>,
+    pub Kills_Penta: Option<u16>,
+    pub Kills_Phoenix: Option<u16>,
+    pub Kills_Player: Option<u16>,
+    pub Kills_Quadra: Option<u16>,
+    pub Kills_Siege_Juggernaut: Option<u16>,
+    pub Kills_Single: Option<u16>,
+    pub Kills_Triple: Option<u16>,
+    pub Kills_Wild_Juggernaut: Option<u16>,
     pub Map_Game: Option<String>,
-    pub Mastery_Level: Option<u8>,
+    pub Mastery_Level: Option<u16>,
     pub Match: Option<u32>,
     pub Match_Duration: Option<u64>,
+    #[serde(default, deserialize_with = "crate::decode::list")]
     pub MergedPlayers: Option<Vec<MergedPlayer>>,
-    pub Minutes: Option<u8>,
-    pub Multi_kill_Max: Option<u8>,
-    pub Objective_Assists: Option<u8>,
+    pub Minutes: Option<u16>,
+    pub Multi_kill_Max: Option<u16>,
+    pub Objective_Assists: Option<u16>,
     pub PartyId: Option<u32>,
     pub Rank_Stat_Conquest: Option<f32>,
     pub Rank_Stat_Duel: Option<f32>,
@@ -132,7 +136,7 @@ pub struct PlayerMatchDetails {
     pub Region: Option<String>,
     pub Skin: Option<String>,
     pub SkinId: Option<u16>,
-    pub Structure_Damage: Option<u16>,
+    pub Structure_Damage: Option<u32>,
     pub Surrendered: Option<u8>,
     pub TaskForce: Option<u8>,
     pub Team1Score: Option<u64>,
@@ -140,8 +144,8 @@ pub struct PlayerMatchDetails {
     pub TeamId: Option<u32>,
     pub Team_Name: Option<String>,
     pub Time_In_Match_Seconds: Option<u16>,
-    pub Towers_Destroyed: Option<u8>,
-    pub Wards_Placed: Option<u16>,
+    pub Towers_Destroyed: Option<u16>,
+    pub Wards_Placed: Option<u32>,
     pub Win_Status: Option<String>,
     pub Winning_TaskForce: Option<u8>,
     pub hasReplay: Option<char>,
@@ -156,6 +160,13 @@ pub struct PlayerMatchDetails {
     pub ret_msg: Option<String>,
 }
 
+impl PlayerMatchDetails {
+    // None if the queue id is missing or one HiRust doesn't know
+    pub fn queue(&self) -> Option<DataConstants> {
+        self.match_queue_id.and_then(|x| DataConstants::try_from(x).ok())
+    }
+}
+
 #[allow(non_snake_case)]
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct AbilityDescriptionValuePair {
@@ -175,7 +186,9 @@ pub struct ItemDescriptionValuePair {
 pub struct AbilityItemDescription {
     pub cooldown: Option<String>,
     pub cost: Option<String>,
+    #[serde(default, deserialize_with = "crate::decode::list")]
     pub menuitems: Option<Vec<AbilityDescriptionValuePair>>,
+    #[serde(default, deserialize_with = "crate::decode::list")]
     pub rankitems: Option<Vec<AbilityDescriptionValuePair>>,
 }
 
@@ -207,7 +220,7 @@ pub struct God {
     pub Cons: Option<String>,
     pub HP5PerLevel: Option<f32>,
     pub Health: Option<u16>,
-    pub HealthPerFive: Option<u8>,
+    pub HealthPerFive: Option<u16>,
     pub HealthPerLevel: Option<u8>,
     pub MP5PerLevel: Option<f32>,
     pub MagicProtection: Option<u8>,
@@ -234,6 +247,7 @@ pub struct God {
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ItemDescription {
     pub Description: Option<String>,
+    #[serde(default, deserialize_with = "crate::decode::list")]
     pub MenuItems: Option<Vec<ItemDescriptionValuePair>>,
 }
 
@@ -0,0 +1,239 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use std::collections::{HashMap, HashSet};
+
+use crate::clock::{Clock, SystemClock};
+use crate::crawler::CrawledMatchId;
+use crate::endpoints::{GetMatchIdsByQueueRequest, TimeWindow};
+use crate::hi_rez_constants::DataConstants;
+use crate::models::PlayerMatchDetails;
+use crate::request_maker::RequestMaker;
+
+#[cfg(test)]
+use galvanic_test::test_suite;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingMatch {
+    pub match_id: CrawledMatchId,
+    pub first_seen: DateTime<Utc>,
+    pub last_checked: DateTime<Utc>,
+}
+
+#[derive(Debug, Default)]
+pub struct PollResult {
+    // matches that are no longer active, ready for get_match_details
+    pub finished: Vec<CrawledMatchId>,
+    // matches that were given up on after the max age passed
+    pub expired: Vec<PendingMatch>,
+}
+
+/*
+ * Remembers matches that were still being played when their window was requested, and
+ * requests the window again once the recheck delay has passed to find out if they finished
+ */
+pub struct PendingMatchTracker {
+    pending: HashMap<String, PendingMatch>,
+    recheck_delay: Duration,
+    max_age: Duration,
+    clock: Box<dyn Clock>,
+}
+
+impl PendingMatchTracker {
+    pub fn new(recheck_delay: Duration) -> PendingMatchTracker {
+        PendingMatchTracker::with_clock(recheck_delay, Box::new(SystemClock))
+    }
+
+    pub fn with_clock(recheck_delay: Duration, clock: Box<dyn Clock>) -> PendingMatchTracker {
+        PendingMatchTracker {
+            pending: HashMap::new(),
+            recheck_delay,
+            // far longer than any match, so a match still active by then was never recorded
+            max_age: Duration::hours(24),
+            clock,
+        }
+    }
+
+    // stop checking matches this long after they were first seen, 24 hours by default
+    pub fn max_age(mut self, max_age: Duration) -> PendingMatchTracker {
+        self.max_age = max_age;
+        self
+    }
+
+    pub fn pending(&self) -> Vec<&PendingMatch> {
+        self.pending.values().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    // matches that are already tracked keep the time they were first seen
+    pub fn track(&mut self, match_id: CrawledMatchId) {
+        let now = self.clock.now();
+        self.pending
+            .entry(match_id.match_id.clone())
+            .or_insert(PendingMatch {
+                match_id,
+                first_seen: now,
+                last_checked: now,
+            });
+    }
+
+    pub fn track_all(&mut self, match_ids: Vec<CrawledMatchId>) {
+        for match_id in match_ids {
+            self.track(match_id);
+        }
+    }
+
+    /*
+     * Requests the window of every match due to be checked again, returning those that finished
+     */
+    pub fn poll(&mut self, request_maker: &RequestMaker) -> PollResult {
+        let now = self.clock.now();
+        let mut result = PollResult::default();
+
+        let expired: Vec<String> = self
+            .pending
+            .values()
+            .filter(|x| now - x.first_seen >= self.max_age)
+            .map(|x| x.match_id.match_id.clone())
+            .collect();
+        for match_id in expired {
+            result.expired.push(self.pending.remove(&match_id).unwrap());
+        }
+
+        // several pending matches often share a window, which only needs requesting once
+        let mut windows: HashSet<(DataConstants, NaiveDate, TimeWindow)> = HashSet::new();
+        for pending_match in self.pending.values() {
+            if now - pending_match.last_checked >= self.recheck_delay {
+                let match_id = &pending_match.match_id;
+                windows.insert((match_id.queue_id, match_id.date, match_id.window));
+            }
+        }
+        let requests: Vec<GetMatchIdsByQueueRequest> = windows
+            .into_iter()
+            .map(|(queue_id, date, window)| GetMatchIdsByQueueRequest {
+                queue_id,
+                date,
+                window,
+            })
+            .collect();
+
+        for (request, reply) in requests.iter().zip(request_maker.execute(requests.clone())) {
+            let replies = match reply {
+                Ok(replies) => replies,
+                Err(msg) => {
+                    println!("Error checking pending matches: {}", msg);
+                    continue;
+                }
+            };
+
+            for pending_match in self.pending.values_mut() {
+                let match_id = &pending_match.match_id;
+                if match_id.queue_id == request.queue_id
+                    && match_id.date == request.date
+                    && match_id.window == request.window
+                {
+                    pending_match.last_checked = now;
+                }
+            }
+
+            for reply in replies {
+                if let (Some('n'), Some(match_id)) = (reply.Active_Flag, reply.Match) {
+                    if let Some(pending_match) = self.pending.remove(&match_id) {
+                        result.finished.push(pending_match.match_id);
+                    }
+                }
+            }
+        }
+
+        result
+    }
+
+    /*
+     * Polls for finished matches and retrieves their details
+     */
+    pub fn fetch_finished(
+        &mut self,
+        request_maker: &RequestMaker,
+    ) -> Result<Vec<Result<PlayerMatchDetails, String>>, String> {
+        let finished = self.poll(request_maker).finished;
+        if finished.is_empty() {
+            return Ok(Vec::new());
+        }
+        request_maker.get_match_details(finished.into_iter().map(|x| x.match_id).collect())
+    }
+}
+
+#[cfg(test)]
+test_suite! {
+    name test_pending;
+    use super::*;
+    use chrono::TimeZone;
+    use std::sync::Arc;
+    use crate::clock::FixedClock;
+    use crate::reqwest_wrapper::MockWrapper as ReqwestWrapper;
+
+    fn pending_match(match_id: &str) -> CrawledMatchId {
+        CrawledMatchId {
+            match_id: String::from(match_id),
+            queue_id: DataConstants::RankedConquest,
+            date: NaiveDate::from_ymd_opt(2019, 8, 5).unwrap(),
+            window: TimeWindow::ten_minutes(3, 0).unwrap(),
+        }
+    }
+
+    test finished_matches_are_emitted_after_delay() {
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().times(1).returning(|_x| {
+            Ok(String::from(
+                "[{ \"ret_msg\": null, \"Active_Flag\": \"n\", \"Match\": \"1\" },\
+                  { \"ret_msg\": null, \"Active_Flag\": \"y\", \"Match\": \"2\" }]",
+            ))
+        });
+        let request_maker = RequestMaker::mock(reqwest);
+
+        let clock = Arc::new(FixedClock::new(Utc.with_ymd_and_hms(2019, 8, 5, 3, 5, 0).unwrap()));
+        let mut tracker = PendingMatchTracker::with_clock(Duration::minutes(30), Box::new(Arc::clone(&clock)));
+        tracker.track_all(vec![pending_match("1"), pending_match("2")]);
+
+        // nothing is due yet, so nothing is requested
+        assert!(tracker.poll(&request_maker).finished.is_empty());
+
+        clock.set(Utc.with_ymd_and_hms(2019, 8, 5, 3, 35, 0).unwrap());
+        let result = tracker.poll(&request_maker);
+        assert_eq!(result.finished, vec![pending_match("1")]);
+        assert_eq!(tracker.len(), 1);
+        assert_eq!(tracker.pending()[0].last_checked, Utc.with_ymd_and_hms(2019, 8, 5, 3, 35, 0).unwrap());
+        assert_eq!(tracker.pending()[0].first_seen, Utc.with_ymd_and_hms(2019, 8, 5, 3, 5, 0).unwrap());
+
+        // match 2 was only just checked
+        assert!(tracker.poll(&request_maker).finished.is_empty());
+    }
+
+    test old_matches_expire() {
+        let request_maker = RequestMaker::mock(ReqwestWrapper::new());
+        let clock = Arc::new(FixedClock::new(Utc.with_ymd_and_hms(2019, 8, 5, 3, 5, 0).unwrap()));
+        let mut tracker = PendingMatchTracker::with_clock(Duration::hours(1), Box::new(Arc::clone(&clock)))
+            .max_age(Duration::minutes(30));
+        tracker.track(pending_match("1"));
+
+        clock.set(Utc.with_ymd_and_hms(2019, 8, 5, 3, 40, 0).unwrap());
+        let result = tracker.poll(&request_maker);
+        assert_eq!(result.expired.len(), 1);
+        assert!(tracker.is_empty());
+    }
+
+    test matches_expire_after_a_day_by_default() {
+        let request_maker = RequestMaker::mock(ReqwestWrapper::new());
+        let clock = Arc::new(FixedClock::new(Utc.with_ymd_and_hms(2019, 8, 5, 3, 5, 0).unwrap()));
+        let mut tracker = PendingMatchTracker::with_clock(Duration::days(2), Box::new(Arc::clone(&clock)));
+        tracker.track(pending_match("1"));
+
+        clock.set(Utc.with_ymd_and_hms(2019, 8, 6, 3, 5, 0).unwrap());
+        assert_eq!(tracker.poll(&request_maker).expired.len(), 1);
+    }
+}
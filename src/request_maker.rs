@@ -1,15 +1,25 @@
-use chrono::{Date, Datelike, Utc};
 use pbr::ProgressBar;
+use serde::de::DeserializeOwned;
 use std::cmp;
 use std::fs::File;
+use std::collections::VecDeque;
 use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
 use std::sync::{Arc, Mutex};
 use std::thread;
 
-use crate::hi_rez_constants::{DataConstants, ReturnDataType, UrlConstants};
-use crate::models::{GetMatchIdsByQueueReply, PlayerMatchDetails};
-use crate::session_manager::SessionManager;
-use crate::url_builder;
+pub use crate::endpoints::GetMatchIdsByQueueRequest;
+use crate::archive::ResponseArchive;
+use crate::decode::{self, Anomaly};
+use crate::drift::{DriftDetector, DriftReport};
+use crate::endpoints::{Endpoint, GetMatchDetailsBatch};
+use crate::hi_rez_constants::{ReturnDataType, UrlConstants};
+use crate::models::PlayerMatchDetails;
+use crate::session_manager::{SessionManager, SessionProvider};
+
+#[cfg(feature = "async")]
+use futures::SinkExt;
 
 cfg_if::cfg_if! {
     if #[cfg(test)] {
@@ -22,38 +32,45 @@ cfg_if::cfg_if! {
     }
 }
 
-const VALID_HOURS: [&str; 25] = [
-    "-1", "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15",
-    "16", "17", "18", "19", "20", "21", "22", "23",
-];
-const VALID_MINUTES: [&str; 7] = ["", "00", "10", "20", "30", "40", "50"];
+pub(crate) const VALID_RETURN_MESSAGES: [&str; 1] = ["Player Privacy Flag set for this player."];
 
-const VALID_RETURN_MESSAGES: [&str; 1] = ["Player Privacy Flag set for this player."];
+// the most anomalies kept between calls to take_anomalies, after which the oldest are dropped
+pub const MAX_ANOMALIES: usize = 10_000;
 
-fn format_date(date: Date<Utc>) -> String {
-    format!("{}{:02}{:02}", date.year(), date.month(), date.day(),)
-}
+// the response text of a single request
+type RawReply = Result<String, String>;
 
-fn construct_batch_match_id_string(match_ids: Vec<String>) -> String {
-    let mut ret_string = String::from("/");
-    for id in match_ids {
-        ret_string.push_str(&format!("{},", id));
-    }
-    ret_string.truncate(ret_string.len() - 1);
-    ret_string
+/*
+ * Replies paired with the index of the request they answer, in the order they arrive
+ */
+pub struct ReplyStream<T> {
+    // requests that could not be sent at all
+    invalid: std::vec::IntoIter<(usize, Result<T, String>)>,
+    receiver: Receiver<(usize, Result<T, String>)>,
 }
 
-#[derive(Debug, Clone)]
-pub struct GetMatchIdsByQueueRequest {
-    pub queue_id: DataConstants,
-    pub date: Date<Utc>,
-    pub hour: String,
-    pub minute: String,
+impl<T> Iterator for ReplyStream<T> {
+    type Item = (usize, Result<T, String>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.invalid.next() {
+            Some(reply) => Some(reply),
+            // ends once every worker thread has finished
+            None => self.receiver.recv().ok(),
+        }
+    }
 }
 
 pub struct RequestMaker {
-    session_manager: Arc<SessionManager>,
+    session_manager: Arc<dyn SessionProvider>,
     reqwest: Arc<ReqwestWrapper>,
+    data_type: ReturnDataType,
+    archive: Option<Arc<ResponseArchive>>,
+    drift_detector: Option<Arc<DriftDetector>>,
+    // where replies that fail to parse are written, if anywhere
+    debug_dump_dir: Option<PathBuf>,
+    // fields left empty because they didn't fit their model, with the method they came from
+    anomalies: Arc<Mutex<VecDeque<(String, Anomaly)>>>,
 }
 
 impl RequestMaker {
@@ -62,6 +79,24 @@ impl RequestMaker {
         RequestMaker {
             session_manager: Arc::new(session_manager),
             reqwest: Arc::new(ReqwestWrapper {}),
+            data_type: ReturnDataType::Json,
+            archive: None,
+            drift_detector: None,
+            debug_dump_dir: None,
+            anomalies: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    #[cfg(not(test))]
+    pub fn with_provider(session_provider: Arc<dyn SessionProvider>) -> RequestMaker {
+        RequestMaker {
+            session_manager: session_provider,
+            reqwest: Arc::new(ReqwestWrapper {}),
+            data_type: ReturnDataType::Json,
+            archive: None,
+            drift_detector: None,
+            debug_dump_dir: None,
+            anomalies: Arc::new(Mutex::new(VecDeque::new())),
         }
     }
 
@@ -76,66 +111,85 @@ impl RequestMaker {
         RequestMaker {
             session_manager: Arc::new(SessionManager::mock(dummy_reqwest)),
             reqwest: Arc::new(reqwest),
+            data_type: ReturnDataType::Json,
+            archive: None,
+            drift_detector: None,
+            debug_dump_dir: None,
+            anomalies: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    /*
+     * Chooses whether replies are requested as JSON or XML, which needs the xml feature
+     */
+    pub fn set_data_type(&mut self, data_type: ReturnDataType) -> Result<(), String> {
+        if data_type == ReturnDataType::Xml && !cfg!(feature = "xml") {
+            return Err(String::from("HiRust was built without the xml feature"));
         }
+        self.data_type = data_type;
+        Ok(())
+    }
+
+    pub fn data_type(&self) -> &ReturnDataType {
+        &self.data_type
+    }
+
+    /*
+     * Stores the body of every response received from now on in archive
+     */
+    pub fn set_archive(&mut self, archive: ResponseArchive) {
+        self.archive = Some(Arc::new(archive));
+    }
+
+    pub fn archive(&self) -> Option<&ResponseArchive> {
+        self.archive.as_deref()
+    }
+
+    /*
+     * Compares every JSON reply parsed from now on to its model, see drift_report
+     */
+    pub fn set_drift_detector(&mut self, drift_detector: DriftDetector) {
+        self.drift_detector = Some(Arc::new(drift_detector));
+    }
+
+    pub fn drift_report(&self) -> Option<DriftReport> {
+        self.drift_detector.as_ref().map(|x| x.report())
+    }
+
+    /*
+     * Writes the last reply of each data type that failed to parse to dir, as
+     * debug_dump.json or debug_dump.xml
+     */
+    pub fn set_debug_dump_dir<P: AsRef<Path>>(&mut self, dir: P) {
+        self.debug_dump_dir = Some(dir.as_ref().to_path_buf());
+    }
+
+    /*
+     * The fields read as null since this was last called, because their values didn't fit
+     * the models, with the method name of the reply they were in
+     *
+     * Long running callers should call this regularly, as only the last MAX_ANOMALIES are kept
+     */
+    pub fn take_anomalies(&self) -> Vec<(String, Anomaly)> {
+        self.anomalies.lock().unwrap().drain(..).collect()
     }
 
     pub fn get_match_ids_by_queue(
         &mut self,
         requests: Vec<GetMatchIdsByQueueRequest>,
     ) -> Result<Vec<String>, String> {
-        let mut url_optionals: Vec<String> = Vec::new();
-        for request in requests {
-            let queue_id = request.queue_id;
-            let date = request.date;
-            let hour = request.hour;
-            let minute = request.minute;
-
-            let time_window_to_retrieve: String;
-
-            match VALID_HOURS.iter().find(|&&x| x == hour) {
-                Some(_) => {}
-                None => return Err(String::from("Invalid hour specified")),
-            };
-            match VALID_MINUTES.iter().find(|&&x| x == minute) {
-                Some(_) => {}
-                None => return Err(String::from("Invalid minute specified")),
-            };
-
-            if &hour == "-1" && &minute == "" {
-                time_window_to_retrieve = String::from("-1");
-            } else if &hour == "-1" && &minute != "" {
-                return Err(String::from("Invalid combination of hour and minute"));
-            } else {
-                time_window_to_retrieve = format!("{},{}", hour, minute);
-            }
-            url_optionals.push(format!(
-                "/{}/{}/{}",
-                queue_id.val(),
-                format_date(date),
-                time_window_to_retrieve
-            ));
+        // reject the whole batch before any requests are spent on it
+        for request in &requests {
+            request.path()?;
         }
 
-        let responses = self.concurrent_reqwest(UrlConstants::GetMatchIdsByQueue, url_optionals);
-
         let mut all_ids: Vec<String> = Vec::new();
-        for response_text in responses {
-            let replies: Vec<GetMatchIdsByQueueReply> =
-                match serde_json::from_str(&response_text.clone()) {
-                    Ok(json) => json,
-                    Err(msg) => {
-                        let mut file = File::create("debug_dump.json").unwrap();
-                        file.write_all(response_text.as_bytes()).unwrap();
-                        return Err(format!(
-                            "Error deserializing get match ids by queue reply: {}",
-                            msg
-                        ));
-                    }
-                };
+        for reply in self.execute(requests) {
+            let replies = reply?;
 
             // allow the response to be empty
             // this may happen because too many matches were played in the q that day
-            // should probably update this to detect that and get it hourly
+            // MatchIdCrawler detects that and retries hourly
             if replies.len() > 0 {
                 match &replies[0].ret_msg {
                     Some(msg) => return Err(format!("GetMatchIdsByQueue Request Error: {}", msg)),
@@ -165,30 +219,19 @@ impl RequestMaker {
         let num_urls_needed: f32 = match_ids_len / 10_f32;
         let num_urls_needed: usize = num_urls_needed.ceil() as usize;
 
-        let mut id_strings = vec![];
+        let mut batches = vec![];
         for _ in 0..num_urls_needed {
             let limit = cmp::min(match_ids.len(), 10);
-            let ids = match_ids.drain(..limit).collect();
-            id_strings.push(construct_batch_match_id_string(ids));
+            let match_ids = match_ids.drain(..limit).collect();
+            batches.push(GetMatchDetailsBatch { match_ids });
         }
 
-        let responses = self.concurrent_reqwest(UrlConstants::GetMatchDetailsBatch, id_strings);
-
         let mut replies: Vec<Result<PlayerMatchDetails, String>> = Vec::new();
-        for response in responses {
-            let reply: Vec<PlayerMatchDetails> = match serde_json::from_str(&response) {
-                Ok(json) => json,
-                Err(msg) => {
-                    let mut file = File::create("debug_dump.json").unwrap();
-                    file.write_all(response.as_bytes()).unwrap();
-                    replies.push(Err(format!(
-                        "Error deserializing get match details batch reply: {}",
-                        msg
-                    )));
-                    continue;
-                }
-            };
-            replies.append(&mut reply.into_iter().map(|x| Ok(x)).collect());
+        for reply in self.execute(batches) {
+            match reply {
+                Ok(reply) => replies.append(&mut reply.into_iter().map(Ok).collect()),
+                Err(msg) => replies.push(Err(msg)),
+            }
         }
 
         // this might happen if there were too many matches
@@ -211,107 +254,273 @@ impl RequestMaker {
         Ok(replies)
     }
 
-    pub fn concurrent_reqwest(
+    /*
+     * Sends every request concurrently, returning a reply for each in the order they were given
+     */
+    pub fn execute<E: Endpoint>(&self, requests: Vec<E>) -> Vec<Result<E::Response, String>> {
+        let num_requests = requests.len();
+        let mut pb = ProgressBar::new(num_requests as u64);
+        let mut replies: Vec<Option<Result<E::Response, String>>> =
+            (0..num_requests).map(|_| None).collect();
+
+        for (index, reply) in self.stream(requests) {
+            replies[index] = Some(reply);
+            pb.inc();
+        }
+
+        pb.finish();
+        replies
+            .into_iter()
+            .map(|x| x.unwrap_or_else(|| Err(String::from("Request thread panicked"))))
+            .collect()
+    }
+
+    /*
+     * Sends every request concurrently, yielding each reply with the index of its request as
+     * soon as it has been parsed
+     *
+     * Requests are only sent as fast as replies are taken from the stream
+     */
+    pub fn stream<E: Endpoint>(&self, requests: Vec<E>) -> ReplyStream<E::Response> {
+        let data_type = self.data_type.clone();
+        let drift_detector = self.drift_detector.clone();
+        let anomalies = Arc::clone(&self.anomalies);
+        let debug_dump_dir = self.debug_dump_dir.clone();
+        let requests = requests
+            .into_iter()
+            .map(|x| (x.method_name(), x.path()))
+            .collect();
+        self.stream_requests(requests, move |method_name, response_text| {
+            if let (Some(drift_detector), ReturnDataType::Json) = (&drift_detector, &data_type) {
+                E::check_drift(drift_detector, method_name, &response_text);
+            }
+            let (reply, found) = decode(
+                &data_type,
+                debug_dump_dir.as_deref(),
+                method_name,
+                &response_text,
+            )?;
+            if !found.is_empty() {
+                let mut anomalies = anomalies.lock().unwrap();
+                anomalies.extend(found.into_iter().map(|x| (String::from(method_name), x)));
+                let excess = anomalies.len().saturating_sub(MAX_ANOMALIES);
+                anomalies.drain(..excess);
+            }
+            Ok(reply)
+        })
+    }
+
+    /*
+     * Retrieves the details of match_ids 10 at a time, yielding each batch as soon as it arrives
+     */
+    pub fn stream_match_details(
+        &self,
+        match_ids: Vec<String>,
+    ) -> impl Iterator<Item = Result<Vec<PlayerMatchDetails>, String>> {
+        let batches: Vec<GetMatchDetailsBatch> = match_ids
+            .chunks(10)
+            .map(|x| GetMatchDetailsBatch {
+                match_ids: x.to_vec(),
+            })
+            .collect();
+        self.stream(batches).map(|(_, reply)| reply)
+    }
+
+    /*
+     * Like stream, but as a futures Stream for use from async code
+     */
+    #[cfg(feature = "async")]
+    pub fn stream_async<E: Endpoint>(
+        &self,
+        requests: Vec<E>,
+    ) -> impl futures::Stream<Item = (usize, Result<E::Response, String>)> {
+        let replies = self.stream(requests);
+        // bounded like stream, so a slow consumer holds back the requests instead of buffering
+        let (mut sender, receiver) =
+            futures::channel::mpsc::channel(self.session_manager.max_concurrent_sessions());
+        thread::spawn(move || {
+            for reply in replies {
+                if futures::executor::block_on(sender.send(reply)).is_err() {
+                    break;
+                }
+            }
+        });
+        receiver
+    }
+
+    /*
+     * Requests each of url_optionals from endpoint, dropping any that fail
+     */
+    pub fn concurrent_reqwest(&self, endpoint: UrlConstants, url_optionals: Vec<String>) -> Vec<String> {
+        let method_name = endpoint.val();
+        let num_requests = url_optionals.len();
+        let requests = url_optionals
+            .into_iter()
+            .map(|x| (method_name.clone(), Ok(x)))
+            .collect();
+
+        let mut responses: Vec<Option<RawReply>> = vec![None; num_requests];
+        for (index, response) in self.stream_requests(requests, |_, x| Ok(x)) {
+            responses[index] = Some(response);
+        }
+
+        responses
+            .into_iter()
+            .filter_map(|x| match x {
+                Some(Ok(text)) => Some(text),
+                Some(Err(msg)) => {
+                    println!("{}", msg);
+                    None
+                }
+                None => None,
+            })
+            .collect()
+    }
+
+    /*
+     * Sends (method name, url optional) pairs from a pool of at most max_concurrent_sessions
+     * threads, parsing each response with parse on the thread that requested it
+     */
+    fn stream_requests<T, F>(
         &self,
-        endpoint: UrlConstants,
-        mut url_optionals: Vec<String>,
-    ) -> Vec<String> {
-        let arc_endpoint = Arc::new(endpoint);
-        let responses = Arc::new(Mutex::new(Vec::new()));
-        let mut pb = ProgressBar::new(url_optionals.len() as u64);
-
-        let num_requests: f32 = url_optionals.len() as f32;
-        let num_groups_needed: f32 = num_requests / 45_f32;
-        let num_groups_needed: usize = num_groups_needed.ceil() as usize;
-
-        let mut request_groups = vec![];
-        for _ in 0..num_groups_needed {
-            let limit = cmp::min(url_optionals.len(), 45);
-            let urls: Vec<String> = url_optionals.drain(..limit).collect();
-            request_groups.push(urls);
+        requests: Vec<(String, Result<String, String>)>,
+        parse: F,
+    ) -> ReplyStream<T>
+    where
+        T: Send + 'static,
+        F: Fn(&str, String) -> Result<T, String> + Send + Sync + 'static,
+    {
+        let group_size = self.session_manager.max_concurrent_sessions();
+        let (sender, receiver) = mpsc::sync_channel(group_size);
+
+        let mut queue = VecDeque::new();
+        let mut invalid = Vec::new();
+        for (index, (method_name, url_optional)) in requests.into_iter().enumerate() {
+            match url_optional {
+                Ok(url_optional) => queue.push_back((index, method_name, url_optional)),
+                Err(msg) => invalid.push((index, Err(msg))),
+            }
         }
 
-        for request_group in request_groups {
-            let mut handles = vec![];
-            for url_optional in request_group {
-                let session_manager = Arc::clone(&self.session_manager);
-                let reqwest = Arc::clone(&self.reqwest);
-                let endpoint = Arc::clone(&arc_endpoint);
-                let responses = Arc::clone(&responses);
-
-                let handle = thread::spawn(move || {
-                    let mut response_text: String;
-                    loop {
-                        let session_key = match session_manager.get_session_key_concurrent() {
-                            Ok(key) => key,
-                            Err(msg) => {
-                                println!("{}", msg);
-                                return;
-                            }
-                        };
-                        let url = url_builder::url(
-                            &session_manager.credentials.dev_id,
-                            &session_manager.credentials.dev_key,
-                            &String::from(session_key.clone()),
-                            &session_manager.base_url,
-                            &(*endpoint),
-                            &ReturnDataType::Json,
-                            &url_optional,
-                        );
-
-                        response_text = match reqwest.get_to_text(url) {
-                            Ok(text) => text,
-                            Err(msg) => {
-                                println!("{}", msg);
-                                return;
-                            }
-                        };
-
-                        if response_text.contains("Invalid session id") {
-                            session_manager.remove_invalid_session(session_key);
-                        } else {
-                            session_manager.replace_session(session_key);
-                            break;
+        let num_workers = cmp::min(group_size, queue.len());
+        let queue = Arc::new(Mutex::new(queue));
+        let parse = Arc::new(parse);
+        for _ in 0..num_workers {
+            let session_manager = Arc::clone(&self.session_manager);
+            let reqwest = Arc::clone(&self.reqwest);
+            let data_type = self.data_type.clone();
+            let archive = self.archive.clone();
+            let queue = Arc::clone(&queue);
+            let parse = Arc::clone(&parse);
+            let sender = sender.clone();
+
+            thread::spawn(move || loop {
+                let next = queue.lock().unwrap().pop_front();
+                let (index, method_name, url_optional) = match next {
+                    Some(request) => request,
+                    None => return,
+                };
+
+                let reply = RequestMaker::request(
+                    &*session_manager,
+                    &reqwest,
+                    &method_name,
+                    &data_type,
+                    &url_optional,
+                )
+                .inspect(|x| {
+                    if let Some(archive) = &archive {
+                        // losing the copy isn't worth failing a request that was already spent
+                        if let Err(msg) = archive.store(&method_name, &url_optional, &data_type, x) {
+                            println!("Error archiving {} reply: {}", method_name, msg);
                         }
                     }
+                })
+                .and_then(|x| parse(&method_name, x));
 
-                    responses.lock().unwrap().push(response_text);
-                });
-                handles.push(handle);
-            }
+                // stop early if the stream was dropped
+                if sender.send((index, reply)).is_err() {
+                    return;
+                }
+            });
+        }
 
-            for handle in handles {
-                handle.join().unwrap();
-                pb.inc();
-            }
+        ReplyStream {
+            invalid: invalid.into_iter(),
+            receiver,
         }
+    }
 
-        pb.finish();
-        let to_ret = (*responses.lock().unwrap()).clone();
-        to_ret
+    // retries with a new session until HiRez accepts the one used
+    fn request(
+        session_manager: &dyn SessionProvider,
+        reqwest: &ReqwestWrapper,
+        method_name: &str,
+        data_type: &ReturnDataType,
+        url_optional: &str,
+    ) -> RawReply {
+        loop {
+            let session_key = session_manager.get_session_key_concurrent()?;
+            let url = match session_manager.url(
+                &session_key,
+                method_name,
+                data_type,
+                url_optional,
+            ) {
+                Ok(url) => url,
+                Err(msg) => {
+                    session_manager.replace_session(session_key);
+                    return Err(msg);
+                }
+            };
+
+            let response_text = match reqwest.get_to_text(url) {
+                Ok(text) => text,
+                Err(msg) => {
+                    session_manager.replace_session(session_key);
+                    return Err(msg);
+                }
+            };
+
+            if response_text.contains("Invalid session id") {
+                session_manager.remove_invalid_session(session_key);
+            } else {
+                session_manager.replace_session(session_key);
+                return Ok(response_text);
+            }
+        }
     }
 }
 
+fn decode<T: DeserializeOwned>(
+    data_type: &ReturnDataType,
+    debug_dump_dir: Option<&Path>,
+    method_name: &str,
+    response_text: &str,
+) -> Result<(T, Vec<Anomaly>), String> {
+    decode::decode_lenient(data_type, response_text).map_err(|msg| {
+        if let Some(dir) = debug_dump_dir {
+            let path = dir.join(format!("debug_dump.{}", data_type.val()));
+            // the parse error is what gets reported either way
+            let written =
+                File::create(&path).and_then(|mut file| file.write_all(response_text.as_bytes()));
+            if let Err(dump_msg) = written {
+                println!("Error writing {}: {}", path.display(), dump_msg);
+            }
+        }
+        format!("Error deserializing {} reply: {}", method_name, msg)
+    })
+}
+
 #[cfg(test)]
 test_suite! {
     name test_request_maker;
     use super::*;
-    use chrono::TimeZone;
+    use chrono::NaiveDate;
+    use crate::endpoints::{GetGods, TimeWindow};
+    use crate::hi_rez_constants::{DataConstants, Language};
     use crate::test_responses;
 
-    test format_date_correct() {
-        let expected_string = String::from("20190810");
-        let date = Utc.ymd(2019, 8, 10);
-        let generated_string: String = format_date(date);
-        assert_eq!(generated_string, expected_string);
-    }
-
-    test construct_batch_match_id_string_correct() {
-        let match_ids: Vec<String> = vec!["1", "2", "3"].into_iter().map(|x| x.to_string()).collect();
-        let expected_string = String::from("/1,2,3");
-        assert_eq!(expected_string, construct_batch_match_id_string(match_ids));
-    }
-
     fixture match_ids_reqwest() -> RequestMaker {
         setup(&mut self) {
             let mut reqwest = ReqwestWrapper::new();
@@ -320,14 +529,14 @@ test_suite! {
         }
     }
 
-    fixture time_combos(hour: String, minute: String, response: String) -> String {
+    fixture time_combos(window: Result<TimeWindow, String>, response: String) -> String {
         params {
             vec![
-                (String::from("0"),String::from("00"), String::from("956598608")),
-                (String::from("-1"),String::from(""), String::from("956598608")),
-                (String::from("-1"),String::from("20"), String::from("Invalid combination of hour and minute")),
-                (String::from("-2"),String::from("20"), String::from("Invalid hour specified")),
-                (String::from("0"),String::from("23"), String::from("Invalid minute specified")),
+                (TimeWindow::ten_minutes(0, 0), String::from("956598608")),
+                (TimeWindow::hour(0), String::from("956598608")),
+                (Ok(TimeWindow::WholeDay), String::from("956598608")),
+                (TimeWindow::hour(24), String::from("Invalid hour specified")),
+                (TimeWindow::ten_minutes(0, 23), String::from("Invalid minute specified")),
             ].into_iter()
         }
         setup(&mut self) {
@@ -337,13 +546,14 @@ test_suite! {
 
     test get_match_ids_by_queue_valid_time(time_combos, match_ids_reqwest) {
         let mut request_maker = match_ids_reqwest.val;
-        let replies = request_maker
-            .get_match_ids_by_queue(vec![GetMatchIdsByQueueRequest {
+        // invalid windows can't be built, so are never requested
+        let replies = time_combos.params.window.clone().and_then(|window| {
+            request_maker.get_match_ids_by_queue(vec![GetMatchIdsByQueueRequest {
                 queue_id: DataConstants::RankedConquest,
-                date: Utc.ymd(2019, 8, 5),
-                hour: String::from(time_combos.params.hour),
-                minute: String::from(time_combos.params.minute),
-            }]);
+                date: NaiveDate::from_ymd_opt(2019, 8, 5).unwrap(),
+                window,
+            }])
+        });
 
         match replies {
             Ok(response) => assert_eq!(response[0], time_combos.val),
@@ -357,9 +567,8 @@ test_suite! {
                 (
                     vec![GetMatchIdsByQueueRequest {
                         queue_id: DataConstants::RankedConquest,
-                        date: Utc.ymd(2019, 8, 5),
-                        hour: String::from("0"),
-                        minute: String::from("00"),
+                        date: NaiveDate::from_ymd_opt(2019, 8, 5).unwrap(),
+                        window: TimeWindow::ten_minutes(0, 0).unwrap(),
                     }],
                     20
                 ),
@@ -367,15 +576,13 @@ test_suite! {
                     vec![
                         GetMatchIdsByQueueRequest {
                             queue_id: DataConstants::RankedConquest,
-                            date: Utc.ymd(2019, 8, 5),
-                            hour: String::from("0"),
-                            minute: String::from("00"),
+                            date: NaiveDate::from_ymd_opt(2019, 8, 5).unwrap(),
+                            window: TimeWindow::ten_minutes(0, 0).unwrap(),
                         },
                         GetMatchIdsByQueueRequest {
                             queue_id: DataConstants::RankedConquest,
-                            date: Utc.ymd(2019, 8, 5),
-                            hour: String::from("0"),
-                            minute: String::from("00"),
+                            date: NaiveDate::from_ymd_opt(2019, 8, 5).unwrap(),
+                            window: TimeWindow::ten_minutes(0, 0).unwrap(),
                         }
                     ],
                     40
@@ -424,4 +631,141 @@ test_suite! {
 
         assert_eq!(replies.len(), num_ids.val);
     }
+
+    test execute_keeps_request_order() {
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().returning(|x| {
+            let match_id = x.rsplit('/').next().unwrap().to_string();
+            Ok(format!("[{{ \"ret_msg\": null, \"Match\": {} }}]", match_id))
+        });
+        let request_maker = RequestMaker::mock(reqwest);
+
+        let requests: Vec<GetMatchDetailsBatch> = vec![vec!["5"], vec![], vec!["0"], vec!["17"]]
+            .into_iter()
+            .map(|match_ids| GetMatchDetailsBatch {
+                match_ids: match_ids.into_iter().map(String::from).collect(),
+            })
+            .collect();
+        let replies: Vec<Result<Option<u32>, String>> = request_maker
+            .execute(requests)
+            .into_iter()
+            .map(|x| x.map(|mut replies| replies.remove(0).Match))
+            .collect();
+
+        assert_eq!(
+            replies,
+            vec![
+                Ok(Some(5)),
+                Err(String::from("No match ids specified")),
+                Ok(Some(0)),
+                Ok(Some(17)),
+            ]
+        );
+    }
+
+    test execute_reports_decoding_errors() {
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().returning(|_x| Ok(String::from("not json")));
+        let mut request_maker = RequestMaker::mock(reqwest);
+        let dir = std::env::temp_dir().join(format!("hirust-{}-debug-dump", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        request_maker.set_debug_dump_dir(&dir);
+
+        let replies = request_maker.execute(vec![GetGods { language: Language::English }]);
+        assert_eq!(replies.len(), 1);
+        assert!(replies[0].as_ref().unwrap_err().starts_with("Error deserializing getgods reply"));
+        assert_eq!(std::fs::read_to_string(dir.join("debug_dump.json")).unwrap(), "not json");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    test execute_requests_chosen_data_type() {
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().returning(|x| {
+            assert!(x.contains("/getgodsxml/"));
+            Ok(String::from("<ArrayOfGod xmlns:i=\"http://www.w3.org/2001/XMLSchema-instance\"/>"))
+        });
+        let mut request_maker = RequestMaker::mock(reqwest);
+        if request_maker.set_data_type(ReturnDataType::Xml).is_err() {
+            // nothing is requested as XML without the xml feature
+            assert!(!cfg!(feature = "xml"));
+            assert_eq!(request_maker.data_type(), &ReturnDataType::Json);
+            return;
+        }
+
+        let replies = request_maker.execute(vec![GetGods { language: Language::English }]);
+        assert!(replies[0].is_ok());
+    }
+
+    test stream_yields_each_batch() {
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().times(3).returning(|x| {
+            let match_ids = x.rsplit('/').next().unwrap();
+            let replies: Vec<String> = match_ids
+                .split(',')
+                .map(|x| format!("{{ \"ret_msg\": null, \"Match\": {} }}", x))
+                .collect();
+            Ok(format!("[{}]", replies.join(",")))
+        });
+        let request_maker = RequestMaker::mock(reqwest);
+
+        let match_ids: Vec<String> = (1..=25).map(|x| x.to_string()).collect();
+        let mut batch_sizes: Vec<usize> = request_maker
+            .stream_match_details(match_ids)
+            .map(|x| x.unwrap().len())
+            .collect();
+        batch_sizes.sort();
+        assert_eq!(batch_sizes, vec![5, 10, 10]);
+    }
+
+    test stream_reports_invalid_requests() {
+        let request_maker = RequestMaker::mock(ReqwestWrapper::new());
+        let replies: Vec<(usize, String)> = request_maker
+            .stream(vec![GetMatchDetailsBatch { match_ids: Vec::new() }])
+            .map(|(index, reply)| (index, reply.unwrap_err()))
+            .collect();
+        assert_eq!(replies, vec![(0, String::from("No match ids specified"))]);
+    }
+
+    test execute_records_anomalies() {
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().returning(|_x| {
+            Ok(String::from("[{ \"ret_msg\": null, \"Match\": 1, \"Structure_Damage\": -5 }]"))
+        });
+        let request_maker = RequestMaker::mock(reqwest);
+
+        let replies = request_maker.execute(vec![GetMatchDetailsBatch { match_ids: vec![String::from("1")] }]);
+        assert_eq!(replies[0].as_ref().unwrap()[0].Structure_Damage, None);
+
+        let anomalies = request_maker.take_anomalies();
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].0, "getmatchdetailsbatch");
+        assert_eq!(anomalies[0].1.field, "Structure_Damage");
+        assert!(request_maker.take_anomalies().is_empty());
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+test_suite! {
+    name test_request_maker_async;
+    use super::*;
+    use crate::endpoints::GetGods;
+    use crate::hi_rez_constants::Language;
+    use crate::reqwest_wrapper::MockWrapper as ReqwestWrapper;
+    use futures::StreamExt;
+
+    test stream_async_yields_replies() {
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().times(2).returning(|_x| Ok(String::from("[]")));
+        let request_maker = RequestMaker::mock(reqwest);
+
+        let requests = vec![GetGods { language: Language::English }, GetGods { language: Language::German }];
+        let mut replies: Vec<usize> = futures::executor::block_on(
+            request_maker.stream_async(requests).map(|(index, reply)| {
+                assert!(reply.unwrap().is_empty());
+                index
+            }).collect::<Vec<usize>>(),
+        );
+        replies.sort();
+        assert_eq!(replies, vec![0, 1]);
+    }
 }
@@ -1,17 +1,19 @@
-use chrono::Utc;
+use chrono::{DateTime, NaiveDate, Utc};
 use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
 use serde_json;
 use std::collections::VecDeque;
 use std::convert::TryInto;
-use std::fs::File;
-use std::io::prelude::*;
-use std::path::Path;
-use std::sync::Mutex;
-use std::thread::sleep;
-use std::time::Duration;
+use std::process;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
+pub use crate::auth::Auth;
+use crate::clock::{parse_server_timestamp, Clock, SkewCorrectedClock, SystemClock};
 use crate::hi_rez_constants::{LimitConstants, ReturnDataType, UrlConstants};
 use crate::models::CreateSessionReply;
+use crate::session_store::{SessionState, SessionStore, SESSION_STORE_VERSION};
 use crate::url_builder;
 
 cfg_if::cfg_if! {
@@ -19,44 +21,194 @@ cfg_if::cfg_if! {
         use galvanic_test::test_suite;
         use crate::reqwest_wrapper::Wrapper;
         use crate::reqwest_wrapper::MockWrapper as ReqwestWrapper;
+        use crate::session_store::MemorySessionStore;
 
     } else {
         use crate::reqwest_wrapper::ReqwestWrapper;
+        use crate::session_store::FileSessionStore;
     }
 }
 
 #[cfg(not(test))]
-const SECONDS_IN_A_DAY: i64 = 86400;
+const DEFAULT_SESSION_STORE_PATH: &str = "sessions.json";
 
-pub struct Auth {
-    pub dev_id: String,
-    pub dev_key: String,
+// where sessions were kept before the store held counters too
+#[cfg(not(test))]
+const LEGACY_SESSION_STORE_PATH: &str = "sessions.txt";
+
+// how long a session leased from a shared store stays reserved without being returned
+const LEASE_SECONDS: i64 = 300;
+
+// other processes sharing a store can't wake us, so waiters check back this often
+pub(crate) const SHARED_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Lease {
+    pub owner: String,
+    pub expires: i64,
 }
 
-impl Auth {
-    pub fn from_file(path: &str) -> Auth {
-        // open the file specified by cli input
-        let path = Path::new(&path);
-        let mut file = File::open(&path).unwrap();
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Session {
+    session_key: String,
+    creation_timestamp: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lease: Option<Lease>,
+    // seconds the HiRez servers were ahead of the creator's clock, if they said
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    clock_offset: Option<i64>,
+}
+
+impl Session {
+    pub fn new(session_key: String, creation_timestamp: i64) -> Session {
+        Session {
+            session_key,
+            creation_timestamp,
+            lease: None,
+            clock_offset: None,
+        }
+    }
+
+    pub fn lease(&self) -> Option<&Lease> {
+        self.lease.as_ref()
+    }
 
-        // read the contents of the file to a string
-        let mut s = String::new();
-        file.read_to_string(&mut s).unwrap();
+    fn is_leased(&self, now: i64) -> bool {
+        match &self.lease {
+            Some(lease) => lease.expires > now,
+            None => false,
+        }
+    }
 
-        // split the file by line, stripping \n
-        let s: Vec<&str> = s.split("\n").collect();
+    // HiRez stops accepting a session this long after it was created
+    fn is_expired(&self, now: i64) -> bool {
+        now - self.creation_timestamp >= i64::from(LimitConstants::SessionTimeLimit.val())
+    }
 
-        Auth {
-            dev_id: String::from(s[0]),
-            dev_key: String::from(s[1]),
+    fn is_leased_by(&self, owner: &str) -> bool {
+        match &self.lease {
+            Some(lease) => lease.owner == owner,
+            None => false,
         }
     }
+
+    pub fn session_key(&self) -> &str {
+        &self.session_key
+    }
+
+    pub fn creation_timestamp(&self) -> i64 {
+        self.creation_timestamp
+    }
 }
 
-#[derive(Clone)]
-pub struct Session {
-    session_key: String,
-    creation_timestamp: i64,
+// the daily limits are reset by HiRez at midnight UTC
+fn hirez_day(timestamp: i64) -> NaiveDate {
+    DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .date_naive()
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct DailyCounters {
+    pub day: NaiveDate,
+    pub sessions_created: u16,
+    pub num_requests: u16,
+}
+
+impl DailyCounters {
+    // the stored counters if they are for day, otherwise fresh ones
+    fn for_day(counters: Option<DailyCounters>, day: NaiveDate) -> DailyCounters {
+        match counters {
+            Some(counters) if counters.day == day => counters,
+            _ => DailyCounters {
+                day,
+                sessions_created: 0,
+                num_requests: 0,
+            },
+        }
+    }
+}
+
+/*
+ * Wakes threads waiting for a session as soon as one is returned
+ */
+pub(crate) struct SessionWaiter {
+    // counts releases so a release between a failed attempt and the wait is not missed
+    releases: Mutex<u64>,
+    session_released: Condvar,
+}
+
+impl SessionWaiter {
+    pub(crate) fn new() -> SessionWaiter {
+        SessionWaiter {
+            releases: Mutex::new(0),
+            session_released: Condvar::new(),
+        }
+    }
+
+    pub(crate) fn releases(&self) -> u64 {
+        *self.releases.lock().unwrap()
+    }
+
+    pub(crate) fn notify(&self) {
+        *self.releases.lock().unwrap() += 1;
+        self.session_released.notify_one();
+    }
+
+    /*
+     * Blocks until there has been a release since seen_releases, the poll interval elapses or
+     * the deadline passes, returning false only if the deadline had already passed
+     */
+    pub(crate) fn wait(
+        &self,
+        seen_releases: u64,
+        deadline: Option<Instant>,
+        poll_interval: Option<Duration>,
+    ) -> bool {
+        let releases = self.releases.lock().unwrap();
+        if *releases != seen_releases {
+            return true;
+        }
+
+        let now = Instant::now();
+        let remaining = match deadline {
+            Some(deadline) if deadline <= now => return false,
+            Some(deadline) => Some(deadline - now),
+            None => None,
+        };
+
+        let wait_for = match (remaining, poll_interval) {
+            (Some(remaining), Some(poll_interval)) => Some(std::cmp::min(remaining, poll_interval)),
+            (remaining, poll_interval) => remaining.or(poll_interval),
+        };
+        match wait_for {
+            Some(wait_for) => drop(self.session_released.wait_timeout(releases, wait_for).unwrap()),
+            None => drop(self.session_released.wait(releases).unwrap()),
+        };
+        true
+    }
+}
+
+/*
+ * Hands out session keys and signs urls for them, whether the sessions are held by this
+ * process or by a broker elsewhere
+ */
+pub trait SessionProvider: Send + Sync {
+    fn get_session_key_concurrent(&self) -> Result<String, String>;
+    fn replace_session(&self, session_key: String);
+    fn remove_invalid_session(&self, session_key: String);
+    fn url(
+        &self,
+        session_key: &str,
+        method_name: &str,
+        data_type: &ReturnDataType,
+        method_specific: &str,
+    ) -> Result<String, String>;
+
+    // the number of requests that can be in flight at once
+    fn max_concurrent_sessions(&self) -> usize {
+        LimitConstants::ConcurrentSessions.val() as usize
+    }
 }
 
 pub struct SessionManager {
@@ -65,7 +217,13 @@ pub struct SessionManager {
     sessions_created: Mutex<u16>,
     valid_session_count: Mutex<u8>,
     num_requests: Mutex<u16>,
+    counters_day: Mutex<NaiveDate>,
     reqwest: ReqwestWrapper,
+    session_store: Box<dyn SessionStore>,
+    lease_owner: String,
+    session_waiter: SessionWaiter,
+    // kept in line with the HiRez servers, whose clock signatures are checked against
+    clock: SkewCorrectedClock,
     pub credentials: Auth,
     pub base_url: UrlConstants,
 }
@@ -73,110 +231,343 @@ pub struct SessionManager {
 #[cfg(not(test))]
 impl Drop for SessionManager {
     fn drop(&mut self) {
-        self.store();
+        if let Err(msg) = self.store() {
+            println!("{}", msg);
+        }
     }
 }
 
 impl SessionManager {
-    pub fn store(&self) {
-        let mut active_sessions = self.active_sessions.lock().unwrap();
-        let mut idle_sessions = self.idle_sessions.lock().unwrap();
+    /*
+     * Saves all sessions, active and idle, along with the daily counters
+     *
+     * With a shared store the sessions and counters are saved as they change, so this only
+     * gives back the leases held by this SessionManager
+     */
+    pub fn store(&self) -> Result<(), String> {
+        if self.session_store.is_shared() {
+            let mut active_sessions = self.active_sessions.lock().unwrap();
+            let lease_owner = &self.lease_owner;
+            self.session_store.update(&mut |state| {
+                for session in state.sessions.iter_mut() {
+                    if session.is_leased_by(lease_owner) {
+                        session.lease = None;
+                    }
+                }
+                Ok(())
+            })?;
+            active_sessions.clear();
+            return Ok(());
+        }
 
-        let mut file = File::create("sessions.txt").unwrap();
+        let active_sessions = self.active_sessions.lock().unwrap();
+        let idle_sessions = self.idle_sessions.lock().unwrap();
 
-        while let Some(session) = active_sessions.pop() {
-            let session_str = format!("{} {}\n", session.session_key, session.creation_timestamp);
-            file.write_all(session_str.as_bytes()).unwrap();
-        }
+        let mut sessions: Vec<Session> = active_sessions.clone();
+        sessions.extend(idle_sessions.iter().cloned());
 
-        while let Some(session) = idle_sessions.pop_front() {
-            let session_str = format!("{} {}\n", session.session_key, session.creation_timestamp);
-            file.write_all(session_str.as_bytes()).unwrap();
-        }
+        let state = SessionState {
+            version: SESSION_STORE_VERSION,
+            sessions,
+            counters: Some(self.counters()),
+            creating: Vec::new(),
+        };
+        self.session_store.save(&state)
     }
 
     #[cfg(not(test))]
-    fn load() -> VecDeque<Session> {
-        // open the file specified by cli input
-        let path = Path::new("sessions.txt");
-        let mut file = match File::open(&path) {
-            Ok(f) => f,
-            Err(_) => return VecDeque::new(),
-        };
-
-        // read the contents of the file to a string
-        let mut all_text = String::new();
-        file.read_to_string(&mut all_text).unwrap();
-
-        // split the file by line, stripping \n
-        let lines: Vec<&str> = all_text.split("\n").collect();
-
-        let mut idle_sessions = VecDeque::new();
-        for line in lines {
-            let session_vec: Vec<&str> = line.split(" ").collect();
-            if session_vec.len() > 1 {
-                idle_sessions.push_back(Session {
-                    session_key: String::from(session_vec[0]),
-                    creation_timestamp: session_vec[1].parse::<i64>().unwrap(),
-                });
+    pub fn new(credentials: Auth, base_url: UrlConstants) -> SessionManager {
+        let session_store = Box::new(
+            FileSessionStore::new(DEFAULT_SESSION_STORE_PATH).migrate_from(LEGACY_SESSION_STORE_PATH),
+        );
+        let state = match session_store.load() {
+            Ok(state) => state,
+            Err(msg) => {
+                println!("{}", msg);
+                SessionState::default()
             }
-        }
+        };
+        SessionManager::from_state(credentials, base_url, ReqwestWrapper {}, session_store, state)
+    }
 
-        idle_sessions
+    /*
+     * Like new, but tests every stored session with HiRez before any requests are made
+     */
+    #[cfg(not(test))]
+    pub fn new_validated(credentials: Auth, base_url: UrlConstants) -> SessionManager {
+        let session_manager = SessionManager::new(credentials, base_url);
+        session_manager.validate_sessions();
+        session_manager
     }
 
     #[cfg(not(test))]
-    pub fn new(credentials: Auth, base_url: UrlConstants) -> SessionManager {
-        let idle_sessions: VecDeque<Session> = SessionManager::load();
-        let valid_session_count: u8 = idle_sessions.len().try_into().unwrap();
-        let sessions_created: u16 = idle_sessions
+    pub fn with_store(
+        credentials: Auth,
+        base_url: UrlConstants,
+        session_store: Box<dyn SessionStore>,
+    ) -> Result<SessionManager, String> {
+        let state = session_store.load()?;
+        Ok(SessionManager::from_state(
+            credentials,
+            base_url,
+            ReqwestWrapper {},
+            session_store,
+            state,
+        ))
+    }
+
+    #[cfg(test)]
+    pub fn mock(reqwest: ReqwestWrapper) -> SessionManager {
+        SessionManager::mock_with_store(reqwest, Box::new(MemorySessionStore::new()))
+    }
+
+    #[cfg(test)]
+    pub fn mock_with_store(
+        reqwest: ReqwestWrapper,
+        session_store: Box<dyn SessionStore>,
+    ) -> SessionManager {
+        let state = session_store.load().unwrap();
+        let credentials = Auth {
+            dev_id: String::from("dummy"),
+            dev_key: String::from("creds"),
+        };
+        SessionManager::from_state(credentials, UrlConstants::UrlBase, reqwest, session_store, state)
+    }
+
+    fn from_state(
+        credentials: Auth,
+        base_url: UrlConstants,
+        reqwest: ReqwestWrapper,
+        session_store: Box<dyn SessionStore>,
+        state: SessionState,
+    ) -> SessionManager {
+        // sessions in a shared store are leased one at a time rather than held in memory
+        let stored_sessions: Vec<Session> = if session_store.is_shared() {
+            Vec::new()
+        } else {
+            state.sessions
+        };
+        let now = Utc::now().timestamp();
+        let today = hirez_day(now);
+
+        // fall back on the sessions created today if no counters were stored for today
+        let sessions_created_today: u16 = stored_sessions
             .iter()
-            .filter(|x| {
-                let seconds_active = Utc::now().timestamp() - x.creation_timestamp;
-                seconds_active < SECONDS_IN_A_DAY
-            })
+            .filter(|x| hirez_day(x.creation_timestamp) == today)
             .count()
             .try_into()
             .unwrap();
+
+        // expired sessions would only take up room under the concurrent limit
+        let idle_sessions: VecDeque<Session> = stored_sessions
+            .into_iter()
+            .filter(|x| !x.is_expired(now))
+            .map(|x| Session::new(x.session_key, x.creation_timestamp))
+            .collect();
+        let valid_session_count: u8 = idle_sessions.len().try_into().unwrap();
+        let (sessions_created, num_requests) = match state.counters {
+            Some(counters) if counters.day == today => (
+                std::cmp::max(counters.sessions_created, sessions_created_today),
+                counters.num_requests,
+            ),
+            _ => (sessions_created_today, 0),
+        };
+
         SessionManager {
             idle_sessions: Mutex::new(idle_sessions),
             active_sessions: Mutex::new(Vec::new()),
             sessions_created: Mutex::new(sessions_created),
             valid_session_count: Mutex::new(valid_session_count),
-            num_requests: Mutex::new(0),
-            reqwest: ReqwestWrapper {},
+            num_requests: Mutex::new(num_requests),
+            counters_day: Mutex::new(today),
+            reqwest,
+            session_store,
+            lease_owner: format!("{}-{}", process::id(), thread_rng().gen::<u32>()),
+            session_waiter: SessionWaiter::new(),
+            clock: SkewCorrectedClock::new(Box::new(SystemClock)),
             credentials,
             base_url,
         }
     }
 
-    #[cfg(test)]
-    pub fn mock(reqwest: ReqwestWrapper) -> SessionManager {
-        SessionManager {
-            idle_sessions: Mutex::new(VecDeque::new()),
-            active_sessions: Mutex::new(Vec::new()),
-            sessions_created: Mutex::new(0),
-            valid_session_count: Mutex::new(0),
-            num_requests: Mutex::new(0),
-            reqwest,
-            credentials: Auth {
-                dev_id: String::from("dummy"),
-                dev_key: String::from("creds"),
-            },
-            base_url: UrlConstants::UrlBase,
+    /*
+     * Replaces the clock used when signing requests, discarding any observed clock skew
+     */
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock = SkewCorrectedClock::new(clock);
+    }
+
+    pub fn clock(&self) -> &SkewCorrectedClock {
+        &self.clock
+    }
+
+    /*
+     * Returns a snapshot of the daily counters, resetting them if the day has rolled over
+     */
+    pub fn counters(&self) -> DailyCounters {
+        let mut sessions_created = self.sessions_created.lock().unwrap();
+        let mut num_requests = self.num_requests.lock().unwrap();
+        let mut counters_day = self.counters_day.lock().unwrap();
+        SessionManager::roll_over_counters(
+            &mut counters_day,
+            &mut sessions_created,
+            &mut num_requests,
+            hirez_day(Utc::now().timestamp()),
+        );
+
+        DailyCounters {
+            day: *counters_day,
+            sessions_created: *sessions_created,
+            num_requests: *num_requests,
+        }
+    }
+
+    fn roll_over_counters(
+        counters_day: &mut NaiveDate,
+        sessions_created: &mut u16,
+        num_requests: &mut u16,
+        today: NaiveDate,
+    ) {
+        if *counters_day != today {
+            *counters_day = today;
+            *sessions_created = 0;
+            *num_requests = 0;
+        }
+    }
+
+    /*
+     * Tests every stored session concurrently, discarding the ones HiRez no longer accepts
+     *
+     * Sessions that could not be tested are kept. Returns the number of sessions discarded.
+     */
+    pub fn validate_sessions(&self) -> usize {
+        let now = Utc::now().timestamp();
+        let sessions: Vec<Session> = if self.session_store.is_shared() {
+            match self.session_store.load() {
+                Ok(state) => state.sessions.into_iter().filter(|x| !x.is_leased(now)).collect(),
+                Err(msg) => {
+                    println!("{}", msg);
+                    return 0;
+                }
+            }
+        } else {
+            self.idle_sessions.lock().unwrap().iter().cloned().collect()
+        };
+
+        let results: Vec<(String, Result<bool, String>)> = thread::scope(|scope| {
+            let handles: Vec<_> = sessions
+                .iter()
+                .map(|x| scope.spawn(move || (x.session_key.clone(), self.test_session(&x.session_key))))
+                .collect();
+            handles.into_iter().map(|x| x.join().unwrap()).collect()
+        });
+
+        let mut invalid_keys: Vec<String> = Vec::new();
+        for (session_key, result) in results {
+            match result {
+                Ok(true) => {}
+                Ok(false) => invalid_keys.push(session_key),
+                Err(msg) => println!("Error testing session {}: {}", session_key, msg),
+            }
+        }
+
+        // every test spends a request
+        let num_tested: u16 = sessions.len().try_into().unwrap();
+        if self.session_store.is_shared() {
+            let today = hirez_day(now);
+            let result = self.session_store.update(&mut |state| {
+                state.sessions.retain(|x| !invalid_keys.contains(&x.session_key));
+                let mut counters = DailyCounters::for_day(state.counters.take(), today);
+                counters.num_requests += num_tested;
+                self.mirror_counters(&counters);
+                state.counters = Some(counters);
+                Ok(())
+            });
+            if let Err(msg) = result {
+                println!("{}", msg);
+            }
+        } else {
+            let mut idle_sessions = self.idle_sessions.lock().unwrap();
+            // a session handed out while it was being tested is no longer idle, so isn't removed
+            let num_idle = idle_sessions.len();
+            idle_sessions.retain(|x| !invalid_keys.contains(&x.session_key));
+            let num_removed: u8 = (num_idle - idle_sessions.len()).try_into().unwrap();
+            *self.valid_session_count.lock().unwrap() -= num_removed;
+            drop(idle_sessions);
+            // roll the counters over first so the tests count toward today
+            self.counters();
+            *self.num_requests.lock().unwrap() += num_tested;
+            self.persist();
         }
+
+        invalid_keys.len()
+    }
+
+    fn test_session(&self, session_key: &str) -> Result<bool, String> {
+        let url = SessionProvider::url(
+            self,
+            session_key,
+            &UrlConstants::TestSession.val(),
+            &ReturnDataType::Json,
+            "",
+        )?;
+        let response_text = self.reqwest.get_to_text(url)?;
+        Ok(response_text.contains("successful test"))
+    }
+
+    pub fn is_shared(&self) -> bool {
+        self.session_store.is_shared()
+    }
+
+    pub fn active_session_count(&self) -> usize {
+        self.active_sessions.lock().unwrap().len()
+    }
+
+    fn mirror_counters(&self, counters: &DailyCounters) {
+        *self.sessions_created.lock().unwrap() = counters.sessions_created;
+        *self.num_requests.lock().unwrap() = counters.num_requests;
+        *self.counters_day.lock().unwrap() = counters.day;
     }
 
     /*
      * Retrieves the first valid session, creating if necessary
      */
     pub fn get_session_key(&self) -> Result<String, String> {
+        if self.session_store.is_shared() {
+            return self.get_shared_session_key();
+        }
+
+        // the counters are saved as they change, so a crash doesn't forget today's requests
+        let key = self.get_local_session_key()?;
+        self.persist();
+        Ok(key)
+    }
+
+    fn persist(&self) {
+        if let Err(msg) = self.store() {
+            println!("{}", msg);
+        }
+    }
+
+    fn get_local_session_key(&self) -> Result<String, String> {
+
         let mut active_sessions = self.active_sessions.lock().unwrap();
         let mut idle_sessions = self.idle_sessions.lock().unwrap();
         let mut valid_session_count = self.valid_session_count.lock().unwrap();
         let num_sessions: u16 = (*valid_session_count).try_into().unwrap();
         let mut sessions_created = self.sessions_created.lock().unwrap();
         let mut num_requests = self.num_requests.lock().unwrap();
+        let mut counters_day = self.counters_day.lock().unwrap();
+        SessionManager::roll_over_counters(
+            &mut counters_day,
+            &mut sessions_created,
+            &mut num_requests,
+            hirez_day(Utc::now().timestamp()),
+        );
+
+        if *num_requests >= LimitConstants::RequestsPerDay.val() {
+            return Err(String::from("Maximum number of requests per day reached"));
+        }
 
         match idle_sessions.pop_front() {
             Some(session) => {
@@ -190,8 +581,6 @@ impl SessionManager {
 
         if *sessions_created >= LimitConstants::SessionsPerDay.val() {
             return Err(String::from("Maximum number of sessions per day reached"));
-        } else if *num_requests >= LimitConstants::RequestsPerDay.val() {
-            return Err(String::from("Maximum number of requests per day reached"));
         } else if num_sessions >= LimitConstants::ConcurrentSessions.val() {
             return Err(String::from("No sessions available"));
         } else {
@@ -210,32 +599,237 @@ impl SessionManager {
         }
     }
 
+    /*
+     * Leases the first session no other SessionManager is using, creating one if the shared
+     * limits allow
+     *
+     * The store is only locked while a slot is reserved and while the new session is added,
+     * not while HiRez is asked for it
+     */
+    fn get_shared_session_key(&self) -> Result<String, String> {
+        let now = Utc::now().timestamp();
+        let today = hirez_day(now);
+        let lease = Lease {
+            owner: self.lease_owner.clone(),
+            expires: now + LEASE_SECONDS,
+        };
+        let reservation = Lease {
+            owner: format!("{}-{}", self.lease_owner, thread_rng().gen::<u32>()),
+            expires: now + LEASE_SECONDS,
+        };
+        let mut leased: Option<(Session, DailyCounters)> = None;
+
+        self.session_store.update(&mut |state| {
+            SessionManager::prune_shared(state, now);
+            let mut counters = DailyCounters::for_day(state.counters.take(), today);
+
+            if counters.num_requests >= LimitConstants::RequestsPerDay.val() {
+                return Err(String::from("Maximum number of requests per day reached"));
+            }
+
+            match state.sessions.iter_mut().find(|x| !x.is_leased(now)) {
+                Some(session) => {
+                    session.lease = Some(lease.clone());
+                    counters.num_requests += 1;
+                    leased = Some((session.clone(), counters.clone()));
+                }
+                None => {
+                    if counters.sessions_created >= LimitConstants::SessionsPerDay.val() {
+                        return Err(String::from("Maximum number of sessions per day reached"));
+                    } else if state.sessions.len() + state.creating.len()
+                        >= LimitConstants::ConcurrentSessions.val() as usize
+                    {
+                        return Err(String::from("No sessions available"));
+                    }
+
+                    state.creating.push(reservation.clone());
+                    counters.sessions_created += 1;
+                }
+            }
+
+            state.counters = Some(counters);
+            Ok(())
+        })?;
+
+        let (session, counters) = match leased {
+            Some(leased) => leased,
+            None => self.add_created_session(&lease, &reservation, today)?,
+        };
+
+        self.mirror_counters(&counters);
+        // the session may have been created by another process, which saw the skew
+        if let Some(offset) = session.clock_offset {
+            self.clock.set_offset(chrono::Duration::seconds(offset));
+        }
+        let key = session.session_key.clone();
+        self.active_sessions.lock().unwrap().push(session);
+        Ok(key)
+    }
+
+    // creates the session a slot was reserved for on reserved_day and adds it to the shared store
+    fn add_created_session(
+        &self,
+        lease: &Lease,
+        reservation: &Lease,
+        reserved_day: NaiveDate,
+    ) -> Result<(Session, DailyCounters), String> {
+        let created = self.create_session();
+        let now = Utc::now().timestamp();
+        let today = hirez_day(now);
+        let mut added: Option<(Session, DailyCounters)> = None;
+
+        self.session_store.update(&mut |state| {
+            state.creating.retain(|x| x.owner != reservation.owner);
+            let mut counters = DailyCounters::for_day(state.counters.take(), today);
+            match &created {
+                Ok(session) => {
+                    let mut session = session.clone();
+                    session.lease = Some(lease.clone());
+                    state.sessions.push(session.clone());
+                    counters.num_requests += 1;
+                    added = Some((session, counters.clone()));
+                }
+                // HiRez did not count a session it refused to create
+                Err(_) => {
+                    if counters.day == reserved_day {
+                        counters.sessions_created = counters.sessions_created.saturating_sub(1);
+                    }
+                }
+            }
+            state.counters = Some(counters);
+            Ok(())
+        })?;
+
+        created?;
+        added.ok_or_else(|| String::from("Shared session store update did not run"))
+    }
+
+    /*
+     * Drops sessions HiRez has expired, unless someone is still using them, along with the
+     * slots of creations that never finished
+     */
+    fn prune_shared(state: &mut SessionState, now: i64) {
+        state
+            .sessions
+            .retain(|x| !x.is_expired(now) || x.is_leased(now));
+        state.creating.retain(|x| x.expires > now);
+    }
+
+    /*
+     * Extends the lease on a shared session once half of it has passed, so a session in use
+     * is never handed to someone else
+     */
+    fn renew_lease(&self, session_key: &str) {
+        let now = Utc::now().timestamp();
+        let mut active_sessions = self.active_sessions.lock().unwrap();
+        let session = match active_sessions
+            .iter_mut()
+            .find(|x| x.session_key == session_key)
+        {
+            Some(session) => session,
+            None => return,
+        };
+        match &session.lease {
+            Some(lease) if lease.expires - now <= LEASE_SECONDS / 2 => {}
+            _ => return,
+        }
+
+        let expires = now + LEASE_SECONDS;
+        let lease_owner = &self.lease_owner;
+        let result = self.session_store.update(&mut |state| {
+            match state
+                .sessions
+                .iter_mut()
+                .find(|x| x.session_key == session_key && x.is_leased_by(lease_owner))
+            {
+                Some(stored) => {
+                    stored.lease = Some(Lease {
+                        owner: lease_owner.clone(),
+                        expires,
+                    });
+                    Ok(())
+                }
+                None => Err(format!("Lease on session {} was lost", session_key)),
+            }
+        });
+
+        match result {
+            Ok(()) => {
+                session.lease = Some(Lease {
+                    owner: lease_owner.clone(),
+                    expires,
+                })
+            }
+            Err(msg) => println!("{}", msg),
+        }
+    }
+
+    fn release_lease(&self, session_key: &str, invalid: bool) {
+        let lease_owner = &self.lease_owner;
+        let result = self.session_store.update(&mut |state| {
+            if invalid {
+                state.sessions.retain(|x| x.session_key != session_key);
+            } else if let Some(session) = state
+                .sessions
+                .iter_mut()
+                .find(|x| x.session_key == session_key && x.is_leased_by(lease_owner))
+            {
+                session.lease = None;
+            }
+            Ok(())
+        });
+
+        if let Err(msg) = result {
+            println!("{}", msg);
+        }
+    }
+
     pub fn get_session_key_concurrent(&self) -> Result<String, String> {
-        let mut rng = thread_rng();
+        self.get_session_key_timeout(None)
+    }
+
+    /*
+     * Retrieves a session, waiting until one is returned if all are in use
+     */
+    pub fn get_session_key_timeout(&self, timeout: Option<Duration>) -> Result<String, String> {
+        let deadline = timeout.map(|x| Instant::now() + x);
+        let poll_interval = if self.session_store.is_shared() {
+            Some(SHARED_POLL_INTERVAL)
+        } else {
+            None
+        };
+
         loop {
+            let seen_releases = self.session_waiter.releases();
             match self.get_session_key() {
                 Ok(key) => return Ok(key),
-                // sleep for one second and between 0 and 5 nanoseconds
                 Err(msg) => {
-                    if msg == String::from("No sessions available") {
-                        sleep(Duration::new(1, rng.gen_range(0, 5)));
-                    } else {
+                    if msg != "No sessions available" {
                         return Err(msg);
                     }
                 }
             };
+
+            if !self.session_waiter.wait(seen_releases, deadline, poll_interval) {
+                return Err(String::from("Timed out waiting for a session"));
+            }
         }
     }
 
     pub fn replace_session(&self, session_key: String) {
         let mut active_sessions = self.active_sessions.lock().unwrap();
-        let mut idle_sessions = self.idle_sessions.lock().unwrap();
         let index = active_sessions
             .iter()
             .position(|x| x.session_key == session_key)
             .unwrap();
-        idle_sessions.push_back(active_sessions[index].clone());
-        active_sessions.remove(index);
+        let session = active_sessions.remove(index);
+
+        if self.session_store.is_shared() {
+            self.release_lease(&session_key, false);
+        } else {
+            self.idle_sessions.lock().unwrap().push_back(session);
+        }
+        self.session_waiter.notify();
     }
 
     pub fn remove_invalid_session(&self, session_key: String) {
@@ -245,53 +839,117 @@ impl SessionManager {
             .position(|x| x.session_key == session_key)
             .unwrap();
         active_sessions.remove(index);
-        *self.valid_session_count.lock().unwrap() -= 1;
+
+        if self.session_store.is_shared() {
+            self.release_lease(&session_key, true);
+        } else {
+            *self.valid_session_count.lock().unwrap() -= 1;
+        }
+
+        // a new session can be created in its place
+        self.session_waiter.notify();
     }
 
     fn create_session(&self) -> Result<Session, String> {
-        let url = url_builder::session_url(
-            &self.base_url,
-            &ReturnDataType::Json,
-            &self.credentials.dev_id,
-            &self.credentials.dev_key,
-        );
+        // a request rejected for its timestamp is sent once more with the corrected clock
+        let mut retried = false;
+        loop {
+            let url = url_builder::session_url(
+                &self.base_url,
+                &ReturnDataType::Json,
+                &self.credentials.dev_id,
+                &self.credentials.dev_key,
+                &self.clock,
+            );
 
-        let response_text: String = match self.reqwest.get_to_text(url) {
-            Ok(text) => text,
-            Err(msg) => return Err(msg),
-        };
+            let response_text: String = self.reqwest.get_to_text(url)?;
 
-        let reply: CreateSessionReply = match serde_json::from_str(&response_text.clone()) {
-            Ok(json) => json,
-            Err(msg) => return Err(format!("Error deserializing create session reply: {}", msg)),
-        };
+            let reply: CreateSessionReply = match serde_json::from_str(&response_text.clone()) {
+                Ok(json) => json,
+                Err(msg) => return Err(format!("Error deserializing create session reply: {}", msg)),
+            };
 
-        match reply.ret_msg {
-            Some(msg) => {
-                if msg != String::from("Approved") {
-                    return Err(format!("CreateSession Request Error: {}", msg));
+            // signatures are rejected when our clock drifts too far from the server's, and the
+            // reply still says what time the server has
+            let mut observed = false;
+            if let Some(timestamp) = &reply.timestamp {
+                match parse_server_timestamp(timestamp) {
+                    Ok(server_time) => {
+                        self.clock.observe_server_time(server_time);
+                        observed = true;
+                    }
+                    Err(msg) => println!("{}", msg),
                 }
             }
-            None => {
-                return Err(String::from(
-                    "CreateSession Request Error: ret_msg was null",
-                ))
+
+            match reply.ret_msg {
+                Some(msg) => {
+                    if msg != "Approved" {
+                        if observed && !retried && msg.to_lowercase().contains("timestamp") {
+                            retried = true;
+                            continue;
+                        }
+                        return Err(format!("CreateSession Request Error: {}", msg));
+                    }
+                }
+                None => {
+                    return Err(String::from(
+                        "CreateSession Request Error: ret_msg was null",
+                    ))
+                }
             }
-        }
 
-        let key = match reply.session_id {
-            Some(key) => key,
-            None => {
-                return Err(String::from(
-                    "CreateSession Request Error: session_id was null",
-                ))
+            let key = match reply.session_id {
+                Some(key) => key,
+                None => {
+                    return Err(String::from(
+                        "CreateSession Request Error: session_id was null",
+                    ))
+                }
+            };
+
+            let mut session = Session::new(key, Utc::now().timestamp());
+            if observed {
+                session.clock_offset = Some(self.clock.offset().num_seconds());
             }
-        };
+            return Ok(session);
+        }
+    }
+}
+
+impl SessionProvider for SessionManager {
+    fn get_session_key_concurrent(&self) -> Result<String, String> {
+        SessionManager::get_session_key_concurrent(self)
+    }
+
+    fn replace_session(&self, session_key: String) {
+        SessionManager::replace_session(self, session_key)
+    }
+
+    fn remove_invalid_session(&self, session_key: String) {
+        SessionManager::remove_invalid_session(self, session_key)
+    }
 
-        Ok(Session {
-            session_key: key,
-            creation_timestamp: Utc::now().timestamp(),
-        })
+    fn url(
+        &self,
+        session_key: &str,
+        method_name: &str,
+        data_type: &ReturnDataType,
+        method_specific: &str,
+    ) -> Result<String, String> {
+        if self.session_store.is_shared() {
+            self.renew_lease(session_key);
+        }
+        Ok(url_builder::url(
+            &self.credentials.dev_id,
+            &self.credentials.dev_key,
+            &session_key.to_string(),
+            &self.base_url,
+            method_name,
+            data_type,
+            &method_specific.to_string(),
+            &self.clock,
+        ))
     }
 }
 
@@ -299,7 +957,12 @@ impl SessionManager {
 test_suite! {
     name test_session_manager;
     use super::*;
+    use chrono::TimeZone;
     use rand::{thread_rng, Rng};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use crate::clock::FixedClock;
+    use crate::session_store::SharedFileSessionStore;
 
     fixture create_sm() -> SessionManager {
         setup(&mut self) {
@@ -395,4 +1058,317 @@ test_suite! {
         assert!({ session_manager.active_sessions.lock().unwrap().len() == 0 });
         assert!({ session_manager.idle_sessions.lock().unwrap().len() == 0 });
     }
+    test counters_roll_over_at_day_boundary(create_sm) {
+        let session_manager = create_sm.val;
+
+        session_manager.get_session_key().unwrap();
+        assert!({ *session_manager.sessions_created.lock().unwrap() == 1 });
+        assert!({ *session_manager.num_requests.lock().unwrap() == 1 });
+
+        *session_manager.counters_day.lock().unwrap() = NaiveDate::from_ymd_opt(2019, 8, 10).unwrap();
+
+        let counters = session_manager.counters();
+        assert_eq!(counters.day, hirez_day(Utc::now().timestamp()));
+        assert_eq!(counters.sessions_created, 0);
+        assert_eq!(counters.num_requests, 0);
+    }
+
+    test counters_are_saved_as_they_change() {
+        let session_store = Arc::new(MemorySessionStore::new());
+        let session_manager = SessionManager::mock_with_store(approving_reqwest("1234"), Box::new(Arc::clone(&session_store)));
+
+        session_manager.get_session_key().unwrap();
+        let counters = session_store.state().counters.unwrap();
+        assert_eq!(counters.sessions_created, 1);
+        assert_eq!(counters.num_requests, 1);
+    }
+
+    test store_saves_sessions_and_counters() {
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().return_const(Ok(String::from(
+            "{ \"ret_msg\": \"Approved\", \"session_id\": \"1234567890\", \"timestamp\": null }",
+        )));
+        let session_store = Arc::new(MemorySessionStore::new());
+        let session_manager = SessionManager::mock_with_store(reqwest, Box::new(Arc::clone(&session_store)));
+
+        let key = session_manager.get_session_key().unwrap();
+        session_manager.store().unwrap();
+
+        let state = session_store.state();
+        assert_eq!(state.sessions.len(), 1);
+        assert_eq!(state.sessions[0].session_key(), key);
+        assert_eq!(state.counters.unwrap().num_requests, 1);
+    }
+
+    fixture shared_store(name: &'static str) -> SharedFileSessionStore {
+        setup(&mut self) {
+            let mut path = std::env::temp_dir();
+            path.push(format!("hirust-{}-{}", process::id(), self.name));
+            let _ = std::fs::remove_file(&path);
+            SharedFileSessionStore::new(path)
+        }
+        tear_down(&self) {
+            let mut path = std::env::temp_dir();
+            path.push(format!("hirust-{}-{}", process::id(), self.name));
+            let _ = std::fs::remove_file(&path);
+            let _ = std::fs::remove_file(path.with_extension("json.lock"));
+        }
+    }
+
+    fn approving_reqwest(session_id: &str) -> ReqwestWrapper {
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().return_const(Ok(format!(
+            "{{ \"ret_msg\": \"Approved\", \"session_id\": \"{}\", \"timestamp\": null }}",
+            session_id
+        )));
+        reqwest
+    }
+
+    test shared_store_leases_sessions_once(shared_store("leases.json")) {
+        let path = shared_store.val.path().to_path_buf();
+        let first_manager = SessionManager::mock_with_store(
+            approving_reqwest("first"),
+            Box::new(SharedFileSessionStore::new(&path)),
+        );
+        let second_manager = SessionManager::mock_with_store(
+            approving_reqwest("second"),
+            Box::new(SharedFileSessionStore::new(&path)),
+        );
+
+        let first_key = first_manager.get_session_key().unwrap();
+        let second_key = second_manager.get_session_key().unwrap();
+        assert_eq!(first_key, "first");
+        assert_eq!(second_key, "second");
+
+        first_manager.replace_session(first_key.clone());
+        let reused_key = second_manager.get_session_key().unwrap();
+        assert_eq!(first_key, reused_key);
+
+        let state = SharedFileSessionStore::new(&path).load().unwrap();
+        assert_eq!(state.sessions.len(), 2);
+        assert_eq!(state.counters.clone().unwrap().sessions_created, 2);
+        assert_eq!(state.counters.unwrap().num_requests, 3);
+        assert_eq!(second_manager.counters().num_requests, 3);
+
+        second_manager.store().unwrap();
+        let state = SharedFileSessionStore::new(&path).load().unwrap();
+        assert!(state.sessions.iter().all(|x| x.lease().is_none()));
+    }
+
+    test shared_store_is_unlocked_while_creating(shared_store("unlocked.json")) {
+        let path = shared_store.val.path().to_path_buf();
+        let lock_path = path.with_extension("json.lock");
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().returning(move |_x| {
+            let lock_file = std::fs::File::open(&lock_path).unwrap();
+            assert!(fs2::FileExt::try_lock_exclusive(&lock_file).is_ok());
+            Ok(String::from("{ \"ret_msg\": \"Approved\", \"session_id\": \"1234\", \"timestamp\": null }"))
+        });
+        let session_manager = SessionManager::mock_with_store(reqwest, Box::new(SharedFileSessionStore::new(&path)));
+
+        assert_eq!(session_manager.get_session_key().unwrap(), "1234");
+        let state = SharedFileSessionStore::new(&path).load().unwrap();
+        assert_eq!(state.sessions.len(), 1);
+        assert!(state.creating.is_empty());
+        assert_eq!(state.counters.unwrap().sessions_created, 1);
+    }
+
+    test shared_store_drops_expired_sessions(shared_store("expired.json")) {
+        let path = shared_store.val.path().to_path_buf();
+        let mut state = SessionState::default();
+        state.sessions.push(Session::new(String::from("expired"), Utc::now().timestamp() - 3600));
+        SharedFileSessionStore::new(&path).save(&state).unwrap();
+
+        let session_manager = SessionManager::mock_with_store(approving_reqwest("1234"), Box::new(SharedFileSessionStore::new(&path)));
+        assert_eq!(session_manager.get_session_key().unwrap(), "1234");
+
+        let state = SharedFileSessionStore::new(&path).load().unwrap();
+        assert_eq!(state.sessions.len(), 1);
+        assert_eq!(state.sessions[0].session_key(), "1234");
+    }
+
+    test shared_leases_are_renewed_on_use(shared_store("renew.json")) {
+        let path = shared_store.val.path().to_path_buf();
+        let session_manager = SessionManager::mock_with_store(approving_reqwest("1234"), Box::new(SharedFileSessionStore::new(&path)));
+        let key = session_manager.get_session_key().unwrap();
+
+        // pretend most of the lease has passed
+        let expires = Utc::now().timestamp() + 10;
+        let store = SharedFileSessionStore::new(&path);
+        store.update(&mut |state| {
+            state.sessions[0].lease.as_mut().unwrap().expires = expires;
+            Ok(())
+        }).unwrap();
+        session_manager.active_sessions.lock().unwrap()[0].lease.as_mut().unwrap().expires = expires;
+
+        SessionProvider::url(&session_manager, &key, "getplayer", &ReturnDataType::Json, "").unwrap();
+        let state = store.load().unwrap();
+        assert!(state.sessions[0].lease().unwrap().expires > expires + LEASE_SECONDS / 2);
+    }
+
+    test load_restores_todays_counters() {
+        let session_store = MemorySessionStore::with_state(SessionState {
+            version: SESSION_STORE_VERSION,
+            sessions: vec![Session::new(String::from("1234567890"), Utc::now().timestamp())],
+            counters: Some(DailyCounters {
+                day: hirez_day(Utc::now().timestamp()),
+                sessions_created: 3,
+                num_requests: 250,
+            }),
+            creating: Vec::new(),
+        });
+        let session_manager = SessionManager::mock_with_store(ReqwestWrapper::new(), Box::new(session_store));
+
+        assert!({ *session_manager.sessions_created.lock().unwrap() == 3 });
+        assert!({ *session_manager.num_requests.lock().unwrap() == 250 });
+        assert!({ *session_manager.valid_session_count.lock().unwrap() == 1 });
+        assert!({ session_manager.idle_sessions.lock().unwrap().len() == 1 });
+    }
+
+    test waiting_thread_gets_returned_session(create_sm) {
+        let session_manager = Arc::new(create_sm.val);
+        let key = session_manager.get_session_key().unwrap();
+
+        // pretend every concurrent session is in use
+        *session_manager.valid_session_count.lock().unwrap() = 45;
+
+        let waiting_manager = Arc::clone(&session_manager);
+        let started = Instant::now();
+        let handle = thread::spawn(move || waiting_manager.get_session_key_timeout(Some(Duration::from_secs(5))));
+
+        thread::sleep(Duration::from_millis(50));
+        session_manager.replace_session(key.clone());
+
+        assert_eq!(handle.join().unwrap(), Ok(key));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    test waiting_times_out(create_sm) {
+        let session_manager = create_sm.val;
+        session_manager.get_session_key().unwrap();
+        *session_manager.valid_session_count.lock().unwrap() = 45;
+
+        let result = session_manager.get_session_key_timeout(Some(Duration::from_millis(50)));
+        assert_eq!(result, Err(String::from("Timed out waiting for a session")));
+    }
+
+    test validate_sessions_discards_invalid() {
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().returning(|x| {
+            if x.contains("/stale/") {
+                Ok(String::from("\"Invalid session id.\""))
+            } else {
+                Ok(String::from("\"This was a successful test with the following parameters added\""))
+            }
+        });
+        let session_store = MemorySessionStore::with_state(SessionState {
+            version: SESSION_STORE_VERSION,
+            sessions: vec![
+                Session::new(String::from("stale"), Utc::now().timestamp()),
+                Session::new(String::from("fresh"), Utc::now().timestamp()),
+            ],
+            counters: None,
+            creating: Vec::new(),
+        });
+        let session_manager = SessionManager::mock_with_store(reqwest, Box::new(session_store));
+        assert!({ *session_manager.valid_session_count.lock().unwrap() == 2 });
+
+        assert_eq!(session_manager.validate_sessions(), 1);
+
+        assert!({ *session_manager.valid_session_count.lock().unwrap() == 1 });
+        assert!({ *session_manager.num_requests.lock().unwrap() == 2 });
+        assert_eq!(session_manager.get_session_key().unwrap(), "fresh");
+    }
+
+    test validate_sessions_keeps_active_sessions() {
+        // the session is handed out while its test is in flight
+        let (tested_sender, tested_receiver) = mpsc::channel();
+        let (taken_sender, taken_receiver) = mpsc::channel::<()>();
+        let taken_receiver = Mutex::new(taken_receiver);
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().returning(move |_x| {
+            tested_sender.send(()).unwrap();
+            taken_receiver.lock().unwrap().recv().unwrap();
+            Ok(String::from("\"Invalid session id.\""))
+        });
+        let session_store = MemorySessionStore::with_state(SessionState {
+            version: SESSION_STORE_VERSION,
+            sessions: vec![Session::new(String::from("stale"), Utc::now().timestamp())],
+            counters: None,
+            creating: Vec::new(),
+        });
+        let session_manager = SessionManager::mock_with_store(reqwest, Box::new(session_store));
+
+        thread::scope(|scope| {
+            let validating = scope.spawn(|| session_manager.validate_sessions());
+            tested_receiver.recv().unwrap();
+            assert_eq!(session_manager.get_session_key().unwrap(), "stale");
+            taken_sender.send(()).unwrap();
+            assert_eq!(validating.join().unwrap(), 1);
+        });
+
+        assert!({ *session_manager.valid_session_count.lock().unwrap() == 1 });
+        assert!(session_manager.idle_sessions.lock().unwrap().is_empty());
+    }
+
+    test signing_corrects_for_server_clock_skew() {
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().returning(|_x| {
+            Ok(String::from(
+                "{ \"ret_msg\": \"Approved\", \"session_id\": \"1234\", \"timestamp\": \"8/10/2019 12:05:00 PM\" }",
+            ))
+        });
+        let mut session_manager = SessionManager::mock(reqwest);
+        let local_time = Utc.with_ymd_and_hms(2019, 8, 10, 12, 0, 0).unwrap();
+        session_manager.set_clock(Box::new(FixedClock::new(local_time)));
+
+        let session_key = session_manager.get_session_key().unwrap();
+        assert_eq!(session_manager.clock().offset(), chrono::Duration::minutes(5));
+
+        let url = SessionProvider::url(&session_manager, &session_key, "getgods", &ReturnDataType::Json, "/1").unwrap();
+        assert!(url.ends_with("/1234/20190810120500/1"));
+    }
+
+    test timestamp_rejection_is_retried_with_corrected_clock() {
+        let mut reqwest = ReqwestWrapper::new();
+        let mut attempts = 0;
+        reqwest.expect_get_to_text().times(2).returning(move |x| {
+            attempts += 1;
+            if attempts == 1 {
+                assert!(x.ends_with("/20190810120000"));
+                Ok(String::from(
+                    "{ \"ret_msg\": \"Invalid timestamp\", \"session_id\": null, \"timestamp\": \"8/10/2019 12:05:00 PM\" }",
+                ))
+            } else {
+                assert!(x.ends_with("/20190810120500"));
+                Ok(String::from(
+                    "{ \"ret_msg\": \"Approved\", \"session_id\": \"1234\", \"timestamp\": \"8/10/2019 12:05:00 PM\" }",
+                ))
+            }
+        });
+        let mut session_manager = SessionManager::mock(reqwest);
+        let local_time = Utc.with_ymd_and_hms(2019, 8, 10, 12, 0, 0).unwrap();
+        session_manager.set_clock(Box::new(FixedClock::new(local_time)));
+
+        assert_eq!(session_manager.get_session_key().unwrap(), "1234");
+    }
+
+    test shared_sessions_carry_clock_skew(shared_store("skew.json")) {
+        let path = shared_store.val.path().to_path_buf();
+        let mut reqwest = ReqwestWrapper::new();
+        reqwest.expect_get_to_text().times(1).returning(|_x| {
+            Ok(String::from(
+                "{ \"ret_msg\": \"Approved\", \"session_id\": \"1234\", \"timestamp\": \"8/10/2019 12:05:00 PM\" }",
+            ))
+        });
+        let mut first_manager = SessionManager::mock_with_store(reqwest, Box::new(SharedFileSessionStore::new(&path)));
+        first_manager.set_clock(Box::new(FixedClock::new(Utc.with_ymd_and_hms(2019, 8, 10, 12, 0, 0).unwrap())));
+        let key = first_manager.get_session_key().unwrap();
+        first_manager.replace_session(key);
+
+        // never creates a session itself, so only learns the skew from the one it leases
+        let second_manager = SessionManager::mock_with_store(ReqwestWrapper::new(), Box::new(SharedFileSessionStore::new(&path)));
+        assert_eq!(second_manager.get_session_key().unwrap(), "1234");
+        assert_eq!(second_manager.clock().offset(), chrono::Duration::minutes(5));
+    }
 }
@@ -0,0 +1,435 @@
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use serde_json;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, prelude::*, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::session_manager::{DailyCounters, Lease, Session};
+
+#[cfg(test)]
+use galvanic_test::test_suite;
+
+pub const SESSION_STORE_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct SessionState {
+    pub version: u32,
+    pub sessions: Vec<Session>,
+    pub counters: Option<DailyCounters>,
+    // slots held for sessions being created, which count toward the concurrent limit
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub creating: Vec<Lease>,
+}
+
+impl Default for SessionState {
+    fn default() -> SessionState {
+        SessionState {
+            version: SESSION_STORE_VERSION,
+            sessions: Vec::new(),
+            counters: None,
+            creating: Vec::new(),
+        }
+    }
+}
+
+/*
+ * Persists sessions and daily counters between runs of a SessionManager
+ */
+pub trait SessionStore: Send + Sync {
+    fn load(&self) -> Result<SessionState, String>;
+    fn save(&self, state: &SessionState) -> Result<(), String>;
+
+    /*
+     * Applies f to the stored state and saves the result, leaving the state untouched if f fails
+     */
+    fn update(
+        &self,
+        f: &mut dyn FnMut(&mut SessionState) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let mut state = self.load()?;
+        f(&mut state)?;
+        self.save(&state)
+    }
+
+    /*
+     * Shared stores are used by several SessionManagers at once, which must lease sessions
+     * through update rather than keeping them in memory
+     */
+    fn is_shared(&self) -> bool {
+        false
+    }
+}
+
+impl<S: SessionStore + ?Sized> SessionStore for Arc<S> {
+    fn load(&self) -> Result<SessionState, String> {
+        (**self).load()
+    }
+
+    fn save(&self, state: &SessionState) -> Result<(), String> {
+        (**self).save(state)
+    }
+
+    fn update(
+        &self,
+        f: &mut dyn FnMut(&mut SessionState) -> Result<(), String>,
+    ) -> Result<(), String> {
+        (**self).update(f)
+    }
+
+    fn is_shared(&self) -> bool {
+        (**self).is_shared()
+    }
+}
+
+pub struct FileSessionStore {
+    path: PathBuf,
+    legacy_path: Option<PathBuf>,
+}
+
+impl FileSessionStore {
+    pub fn new<P: AsRef<Path>>(path: P) -> FileSessionStore {
+        FileSessionStore {
+            path: path.as_ref().to_path_buf(),
+            legacy_path: None,
+        }
+    }
+
+    /*
+     * Reads the sessions from a file written by older versions, one "key timestamp" per line,
+     * until the first save
+     */
+    pub fn migrate_from<P: AsRef<Path>>(mut self, legacy_path: P) -> FileSessionStore {
+        self.legacy_path = Some(legacy_path.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[cfg(test)]
+    fn temp_path(&self) -> PathBuf {
+        temp_path(&self.path)
+    }
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
+
+/*
+ * Writes everything to a temporary file first so a crash never leaves a partially written file
+ */
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let temp_path = temp_path(path);
+    let mut file = File::create(&temp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)
+}
+
+fn load_legacy(path: &Path) -> Result<SessionState, String> {
+    let all_text = match fs::read_to_string(path) {
+        Ok(all_text) => all_text,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(SessionState::default()),
+        Err(msg) => return Err(format!("Error reading legacy session store: {}", msg)),
+    };
+
+    let mut state = SessionState::default();
+    for line in all_text.lines() {
+        let session_vec: Vec<&str> = line.split(' ').collect();
+        if session_vec.len() > 1 {
+            let creation_timestamp = session_vec[1]
+                .parse::<i64>()
+                .map_err(|msg| format!("Error parsing legacy session store: {}", msg))?;
+            state
+                .sessions
+                .push(Session::new(String::from(session_vec[0]), creation_timestamp));
+        }
+    }
+    Ok(state)
+}
+
+impl SessionStore for FileSessionStore {
+    fn load(&self) -> Result<SessionState, String> {
+        let mut file = match File::open(&self.path) {
+            Ok(f) => f,
+            // nothing has been stored yet
+            Err(ref e) if e.kind() == ErrorKind::NotFound => {
+                return match &self.legacy_path {
+                    Some(legacy_path) => load_legacy(legacy_path),
+                    None => Ok(SessionState::default()),
+                };
+            }
+            Err(msg) => return Err(format!("Error opening session store: {}", msg)),
+        };
+
+        let mut all_text = String::new();
+        file.read_to_string(&mut all_text)
+            .map_err(|msg| format!("Error reading session store: {}", msg))?;
+
+        let state: SessionState = serde_json::from_str(&all_text)
+            .map_err(|msg| format!("Error deserializing session store: {}", msg))?;
+
+        if state.version != SESSION_STORE_VERSION {
+            return Err(format!(
+                "Unsupported session store version: {}",
+                state.version
+            ));
+        }
+
+        Ok(state)
+    }
+
+    fn save(&self, state: &SessionState) -> Result<(), String> {
+        let state_str = serde_json::to_string_pretty(state)
+            .map_err(|msg| format!("Error serializing session store: {}", msg))?;
+
+        write_atomically(&self.path, state_str.as_bytes())
+            .map_err(|msg| format!("Error writing session store: {}", msg))
+    }
+}
+
+#[derive(Default)]
+pub struct MemorySessionStore {
+    state: Mutex<SessionState>,
+}
+
+impl MemorySessionStore {
+    pub fn new() -> MemorySessionStore {
+        MemorySessionStore::default()
+    }
+
+    pub fn with_state(state: SessionState) -> MemorySessionStore {
+        MemorySessionStore {
+            state: Mutex::new(state),
+        }
+    }
+
+    pub fn state(&self) -> SessionState {
+        self.state.lock().unwrap().clone()
+    }
+}
+
+impl SessionStore for MemorySessionStore {
+    fn load(&self) -> Result<SessionState, String> {
+        Ok(self.state())
+    }
+
+    fn save(&self, state: &SessionState) -> Result<(), String> {
+        *self.state.lock().unwrap() = state.clone();
+        Ok(())
+    }
+
+    fn update(
+        &self,
+        f: &mut dyn FnMut(&mut SessionState) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let mut stored_state = self.state.lock().unwrap();
+        let mut state = stored_state.clone();
+        f(&mut state)?;
+        *stored_state = state;
+        Ok(())
+    }
+}
+
+/*
+ * A FileSessionStore guarded by an advisory lock on a sibling .lock file, so that several
+ * processes using the same dev id can share one pool of sessions and one set of daily counters
+ */
+pub struct SharedFileSessionStore {
+    file_store: FileSessionStore,
+    lock_path: PathBuf,
+}
+
+impl SharedFileSessionStore {
+    pub fn new<P: AsRef<Path>>(path: P) -> SharedFileSessionStore {
+        let file_store = FileSessionStore::new(path);
+        let mut file_name = file_store.path().file_name().unwrap_or_default().to_os_string();
+        file_name.push(".lock");
+        let lock_path = file_store.path().with_file_name(file_name);
+        SharedFileSessionStore {
+            file_store,
+            lock_path,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.file_store.path()
+    }
+
+    // the lock is released when the returned file is closed
+    fn lock(&self, exclusive: bool) -> Result<File, String> {
+        let lock_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.lock_path)
+            .map_err(|msg| format!("Error opening session store lock: {}", msg))?;
+
+        let locked = if exclusive {
+            lock_file.lock_exclusive()
+        } else {
+            lock_file.lock_shared()
+        };
+        locked.map_err(|msg| format!("Error locking session store: {}", msg))?;
+
+        Ok(lock_file)
+    }
+}
+
+impl SessionStore for SharedFileSessionStore {
+    fn load(&self) -> Result<SessionState, String> {
+        let _lock = self.lock(false)?;
+        self.file_store.load()
+    }
+
+    fn save(&self, state: &SessionState) -> Result<(), String> {
+        let _lock = self.lock(true)?;
+        self.file_store.save(state)
+    }
+
+    fn update(
+        &self,
+        f: &mut dyn FnMut(&mut SessionState) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let _lock = self.lock(true)?;
+        let mut state = self.file_store.load()?;
+        f(&mut state)?;
+        self.file_store.save(&state)
+    }
+
+    fn is_shared(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+test_suite! {
+    name test_session_store;
+    use super::*;
+    use chrono::NaiveDate;
+    use std::env;
+    use std::process;
+    use std::thread;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let mut path = env::temp_dir();
+        path.push(format!("hirust-{}-{}", process::id(), name));
+        path
+    }
+
+    fixture temp_store(name: &'static str) -> FileSessionStore {
+        setup(&mut self) {
+            let path = temp_path(self.name);
+            let _ = fs::remove_file(&path);
+            FileSessionStore::new(path)
+        }
+        tear_down(&self) {
+            let _ = fs::remove_file(temp_path(self.name));
+        }
+    }
+
+    test file_store_missing_file_is_empty(temp_store("missing.json")) {
+        let state = temp_store.val.load().unwrap();
+        assert_eq!(state, SessionState::default());
+    }
+
+    test file_store_migrates_legacy_sessions(temp_store("migrate.json")) {
+        let legacy_path = temp_path("migrate.txt");
+        fs::write(&legacy_path, "ABCDEF 1565395200\nGHIJKL 1565395300\n").unwrap();
+        let store = FileSessionStore::new(temp_store.val.path()).migrate_from(&legacy_path);
+
+        let state = store.load().unwrap();
+        assert_eq!(state.sessions, vec![
+            Session::new(String::from("ABCDEF"), 1565395200),
+            Session::new(String::from("GHIJKL"), 1565395300),
+        ]);
+
+        // once saved the new file wins
+        store.save(&SessionState::default()).unwrap();
+        assert_eq!(store.load().unwrap(), SessionState::default());
+        let _ = fs::remove_file(&legacy_path);
+    }
+
+    test file_store_reports_unreadable_file(temp_store("unreadable.json")) {
+        fs::create_dir(temp_store.val.path()).unwrap();
+        let loaded = temp_store.val.load();
+        fs::remove_dir(temp_store.val.path()).unwrap();
+        assert!(loaded.is_err());
+    }
+
+    test file_store_round_trip(temp_store("round_trip.json")) {
+        let store = &temp_store.val;
+        let state = SessionState {
+            version: SESSION_STORE_VERSION,
+            sessions: vec![Session::new(String::from("ABCDEF"), 1565395200)],
+            counters: Some(DailyCounters {
+                day: NaiveDate::from_ymd_opt(2019, 8, 10).unwrap(),
+                sessions_created: 12,
+                num_requests: 345,
+            }),
+            creating: Vec::new(),
+        };
+
+        store.save(&state).unwrap();
+        assert!(!store.temp_path().exists());
+        assert_eq!(store.load().unwrap(), state);
+    }
+
+    test file_store_rejects_unknown_version(temp_store("version.json")) {
+        let store = &temp_store.val;
+        fs::write(store.path(), "{ \"version\": 99, \"sessions\": [], \"counters\": null }").unwrap();
+        assert_eq!(store.load(), Err(String::from("Unsupported session store version: 99")));
+    }
+
+    test shared_store_serializes_updates(temp_store("shared.json")) {
+        let path = temp_store.val.path().to_path_buf();
+        let mut handles = vec![];
+        for thread_num in 0..8 {
+            let path = path.clone();
+            handles.push(thread::spawn(move || {
+                // each thread opens its own store, as separate processes would
+                let store = SharedFileSessionStore::new(path);
+                for i in 0..10 {
+                    store.update(&mut |state| {
+                        state.sessions.push(Session::new(format!("{}-{}", thread_num, i), 0));
+                        Ok(())
+                    }).unwrap();
+                }
+            }));
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let store = SharedFileSessionStore::new(&path);
+        assert_eq!(store.load().unwrap().sessions.len(), 80);
+        let _ = fs::remove_file(&store.lock_path);
+    }
+
+    test failed_update_leaves_state_untouched() {
+        let store = MemorySessionStore::new();
+        let result = store.update(&mut |state| {
+            state.sessions.push(Session::new(String::from("ABCDEF"), 0));
+            Err(String::from("No sessions available"))
+        });
+
+        assert_eq!(result, Err(String::from("No sessions available")));
+        assert_eq!(store.state(), SessionState::default());
+    }
+
+    test memory_store_round_trip() {
+        let store = MemorySessionStore::new();
+        let mut state = SessionState::default();
+        state.sessions.push(Session::new(String::from("ABCDEF"), 1565395200));
+
+        store.save(&state).unwrap();
+        assert_eq!(store.load().unwrap(), state);
+    }
+}
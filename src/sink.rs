@@ -0,0 +1,490 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde_json::Value;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::models::PlayerMatchDetails;
+
+#[cfg(feature = "sqlite")]
+use crate::matches::Match;
+#[cfg(feature = "sqlite")]
+use rusqlite::{params, Connection};
+#[cfg(test)]
+use galvanic_test::test_suite;
+
+/*
+ * Somewhere to write match details as they are retrieved, such as from BulkJob::run_to_sink
+ */
+pub trait Sink {
+    // may be called with an empty batch
+    fn write(&mut self, details: &[PlayerMatchDetails]) -> Result<(), String>;
+
+    // makes everything written so far durable
+    fn flush(&mut self) -> Result<(), String>;
+}
+
+fn open_append(path: &Path) -> Result<File, String> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|msg| format!("Error opening {}: {}", path.display(), msg))
+}
+
+/*
+ * Writes one JSON object per line
+ */
+pub struct NdjsonSink {
+    writer: Box<dyn Write>,
+}
+
+impl NdjsonSink {
+    pub fn new(writer: Box<dyn Write>) -> NdjsonSink {
+        NdjsonSink { writer }
+    }
+
+    pub fn gzip(writer: Box<dyn Write>) -> NdjsonSink {
+        NdjsonSink::new(Box::new(GzEncoder::new(writer, Compression::default())))
+    }
+
+    /*
+     * Appends to the file at path, compressing with gzip if it ends in .gz
+     *
+     * Each run appends a separate gzip member, which gzip readers treat as one stream
+     */
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<NdjsonSink, String> {
+        let path = path.as_ref();
+        let file = Box::new(BufWriter::new(open_append(path)?));
+        Ok(match path.extension() {
+            Some(extension) if extension == "gz" => NdjsonSink::gzip(file),
+            _ => NdjsonSink::new(file),
+        })
+    }
+}
+
+impl Sink for NdjsonSink {
+    fn write(&mut self, details: &[PlayerMatchDetails]) -> Result<(), String> {
+        for row in details {
+            serde_json::to_writer(&mut self.writer, row).map_err(|msg| msg.to_string())?;
+            self.writer.write_all(b"\n").map_err(|msg| msg.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.writer.flush().map_err(|msg| msg.to_string())
+    }
+}
+
+// a field as a CSV cell, with lists written as JSON
+fn csv_cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        _ => value.to_string(),
+    }
+}
+
+/*
+ * Writes one row per player with a column for every PlayerMatchDetails field, in name order
+ */
+pub struct CsvSink {
+    writer: csv::Writer<Box<dyn Write>>,
+    write_header: bool,
+}
+
+impl CsvSink {
+    pub fn new(writer: Box<dyn Write>) -> CsvSink {
+        CsvSink {
+            writer: csv::Writer::from_writer(writer),
+            write_header: true,
+        }
+    }
+
+    // appends to the file at path, only writing the header if it was empty
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<CsvSink, String> {
+        let path = path.as_ref();
+        let file = open_append(path)?;
+        let is_empty = file.metadata().map(|x| x.len() == 0).unwrap_or(true);
+
+        let mut sink = CsvSink::new(Box::new(BufWriter::new(file)));
+        sink.write_header = is_empty;
+        Ok(sink)
+    }
+}
+
+impl Sink for CsvSink {
+    fn write(&mut self, details: &[PlayerMatchDetails]) -> Result<(), String> {
+        for row in details {
+            let fields = match serde_json::to_value(row).map_err(|msg| msg.to_string())? {
+                Value::Object(fields) => fields,
+                _ => return Err(String::from("Player match details didn't serialize to an object")),
+            };
+
+            if self.write_header {
+                self.writer
+                    .write_record(fields.keys())
+                    .map_err(|msg| msg.to_string())?;
+                self.write_header = false;
+            }
+            self.writer
+                .write_record(fields.values().map(csv_cell))
+                .map_err(|msg| msg.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.writer.flush().map_err(|msg| msg.to_string())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+const SQLITE_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS matches (
+        match_id INTEGER PRIMARY KEY,
+        queue_id INTEGER,
+        map TEXT,
+        entry_datetime TEXT,
+        duration_seconds INTEGER,
+        first_ban_side TEXT,
+        winning_task_force INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS players (
+        match_id INTEGER NOT NULL REFERENCES matches (match_id),
+        player_index INTEGER NOT NULL,
+        task_force INTEGER NOT NULL,
+        player_id TEXT,
+        player_name TEXT,
+        party_id INTEGER,
+        region TEXT,
+        god_id INTEGER,
+        god_name TEXT,
+        account_level INTEGER,
+        final_match_level INTEGER,
+        kills INTEGER,
+        deaths INTEGER,
+        assists INTEGER,
+        gold_earned INTEGER,
+        gold_per_minute INTEGER,
+        damage_player INTEGER,
+        damage_taken INTEGER,
+        damage_mitigated INTEGER,
+        healing INTEGER,
+        structure_damage INTEGER,
+        wards_placed INTEGER,
+        time_in_match_seconds INTEGER,
+        win_status TEXT,
+        PRIMARY KEY (match_id, player_index)
+    );
+    CREATE TABLE IF NOT EXISTS items (
+        match_id INTEGER NOT NULL REFERENCES matches (match_id),
+        player_index INTEGER NOT NULL,
+        slot INTEGER NOT NULL,
+        active INTEGER NOT NULL,
+        item_id INTEGER NOT NULL,
+        item_name TEXT,
+        PRIMARY KEY (match_id, player_index, active, slot)
+    );
+    CREATE TABLE IF NOT EXISTS bans (
+        match_id INTEGER NOT NULL REFERENCES matches (match_id),
+        ban_order INTEGER NOT NULL,
+        god_id INTEGER NOT NULL,
+        god_name TEXT,
+        PRIMARY KEY (match_id, ban_order)
+    );
+";
+
+// the (slot, active, item id, item name) of every item a player bought, without empty slots
+#[cfg(feature = "sqlite")]
+fn items(row: &PlayerMatchDetails) -> Vec<(usize, bool, u16, Option<String>)> {
+    let items = vec![
+        (row.ItemId1, &row.Item_Purch_1),
+        (row.ItemId2, &row.Item_Purch_2),
+        (row.ItemId3, &row.Item_Purch_3),
+        (row.ItemId4, &row.Item_Purch_4),
+        (row.ItemId5, &row.Item_Purch_5),
+        (row.ItemId6, &row.Item_Purch_6),
+    ];
+    let actives = vec![
+        (row.ActiveId1, &row.Item_Active_1),
+        (row.ActiveId2, &row.Item_Active_2),
+        (row.ActiveId3, &row.Item_Active_3),
+        (row.ActiveId4, &row.Item_Active_4),
+    ];
+
+    let slots = |active: bool, slots: Vec<(Option<u16>, &Option<String>)>| {
+        slots
+            .into_iter()
+            .enumerate()
+            .filter_map(move |(slot, (item_id, item_name))| match item_id {
+                Some(item_id) if item_id != 0 => {
+                    Some((slot + 1, active, item_id, item_name.clone()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut all = slots(false, items);
+    all.extend(slots(true, actives));
+    all
+}
+
+/*
+ * Writes matches, players, their items, and bans to separate tables of a SQLite database
+ *
+ * Writing a match that is already stored replaces it, so a resumed job can't duplicate rows.
+ * Matches whose rows can't be grouped into teams are skipped and kept in skipped, so one bad
+ * match doesn't fail the rest of the write
+ */
+#[cfg(feature = "sqlite")]
+pub struct SqliteSink {
+    connection: Connection,
+    skipped: Vec<String>,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteSink {
+    pub fn new(connection: Connection) -> Result<SqliteSink, String> {
+        connection
+            .execute_batch(SQLITE_SCHEMA)
+            .map_err(|msg| format!("Error creating SQLite tables: {}", msg))?;
+        Ok(SqliteSink {
+            connection,
+            skipped: Vec::new(),
+        })
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<SqliteSink, String> {
+        let path = path.as_ref();
+        let connection = Connection::open(path)
+            .map_err(|msg| format!("Error opening {}: {}", path.display(), msg))?;
+        SqliteSink::new(connection)
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    // why each skipped match couldn't be stored
+    pub fn skipped(&self) -> &[String] {
+        &self.skipped
+    }
+
+    fn insert(transaction: &rusqlite::Transaction, match_: &Match) -> rusqlite::Result<()> {
+        let match_id = match_.match_id;
+        for table in &["players", "items", "bans"] {
+            transaction.execute(
+                &format!("DELETE FROM {} WHERE match_id = ?1", table),
+                params![match_id],
+            )?;
+        }
+        transaction.execute(
+            "INSERT OR REPLACE INTO matches VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                match_id,
+                match_.queue_id,
+                match_.map,
+                match_.entry_datetime,
+                match_.duration_seconds.map(|x| x as i64),
+                match_.first_ban_side,
+                match_.winning_task_force,
+            ],
+        )?;
+
+        for (ban_order, ban) in match_.bans.iter().enumerate() {
+            transaction.execute(
+                "INSERT INTO bans VALUES (?1, ?2, ?3, ?4)",
+                params![match_id, ban_order + 1, ban.god_id, ban.god_name],
+            )?;
+        }
+
+        for (player_index, player) in match_.players().enumerate() {
+            transaction.execute(
+                "INSERT INTO players VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, \
+                 ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24)",
+                params![
+                    match_id,
+                    player_index,
+                    player.TaskForce,
+                    player.playerId,
+                    player.playerName,
+                    player.PartyId,
+                    player.Region,
+                    player.GodId,
+                    player.Reference_Name,
+                    player.Account_Level,
+                    player.Final_Match_Level,
+                    player.Kills_Player,
+                    player.Deaths,
+                    player.Assists,
+                    player.Gold_Earned,
+                    player.Gold_Per_Minute,
+                    player.Damage_Player,
+                    player.Damage_Taken,
+                    player.Damage_Mitigated,
+                    player.Healing,
+                    player.Structure_Damage,
+                    player.Wards_Placed,
+                    player.Time_In_Match_Seconds,
+                    player.Win_Status,
+                ],
+            )?;
+            for (slot, active, item_id, item_name) in items(player) {
+                transaction.execute(
+                    "INSERT INTO items VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![match_id, player_index, slot, active, item_id, item_name],
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl Sink for SqliteSink {
+    // rows without a match id, which only carry a ret_msg, are skipped
+    fn write(&mut self, details: &[PlayerMatchDetails]) -> Result<(), String> {
+        let rows = details.iter().filter(|x| x.Match.is_some()).cloned().collect();
+        let mut matches = Vec::new();
+        for grouped in Match::group(rows) {
+            match grouped {
+                Ok(match_) => matches.push(match_),
+                Err(msg) => {
+                    println!("Skipping match: {}", msg);
+                    self.skipped.push(msg);
+                }
+            }
+        }
+
+        let transaction = self
+            .connection
+            .transaction()
+            .map_err(|msg| msg.to_string())?;
+        for match_ in &matches {
+            SqliteSink::insert(&transaction, match_)
+                .map_err(|msg| format!("Error storing match {}: {}", match_.match_id, msg))?;
+        }
+        transaction.commit().map_err(|msg| msg.to_string())
+    }
+
+    // every write is committed as it is made
+    fn flush(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+test_suite! {
+    name test_sink;
+    use super::*;
+    use flate2::read::MultiGzDecoder;
+    use std::env;
+    use std::fs;
+    use std::io::Read;
+    use std::path::PathBuf;
+    use crate::test_responses;
+
+    fn rows() -> Vec<PlayerMatchDetails> {
+        serde_json::from_str(test_responses::GET_MATCH_DETAILS).unwrap()
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("hirust-{}-{}", std::process::id(), name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    test ndjson_appends_gzip_members() {
+        let path = temp_path("sink.ndjson.gz");
+        let rows = rows();
+        for half in rows.chunks(5) {
+            let mut sink = NdjsonSink::open(&path).unwrap();
+            sink.write(half).unwrap();
+            sink.flush().unwrap();
+        }
+
+        let mut text = String::new();
+        MultiGzDecoder::new(File::open(&path).unwrap())
+            .read_to_string(&mut text)
+            .unwrap();
+        let lines: Vec<PlayerMatchDetails> = text
+            .lines()
+            .map(|x| serde_json::from_str(x).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[9].TaskForce, Some(2));
+        fs::remove_file(&path).unwrap();
+    }
+
+    test csv_writes_header_once() {
+        let path = temp_path("sink.csv");
+        let rows = rows();
+        for half in rows.chunks(5) {
+            let mut sink = CsvSink::open(&path).unwrap();
+            sink.write(half).unwrap();
+            sink.flush().unwrap();
+        }
+
+        let mut reader = csv::Reader::from_path(&path).unwrap();
+        let headers = reader.headers().unwrap().clone();
+        let records: Vec<csv::StringRecord> = reader.records().map(|x| x.unwrap()).collect();
+        assert_eq!(records.len(), 10);
+
+        let column = |name: &str| headers.iter().position(|x| x == name).unwrap();
+        assert_eq!(&records[0][column("Match")], "956598608");
+        assert_eq!(&records[0][column("Ban1")], "Ao Kuang");
+        assert_eq!(&records[9][column("TaskForce")], "2");
+        fs::remove_file(&path).unwrap();
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+test_suite! {
+    name test_sqlite_sink;
+    use super::*;
+    use crate::test_responses;
+
+    fn count(sink: &SqliteSink, table: &str) -> i64 {
+        sink.connection()
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |x| x.get(0))
+            .unwrap()
+    }
+
+    test sqlite_normalizes_matches() {
+        let rows: Vec<PlayerMatchDetails> = serde_json::from_str(test_responses::GET_MATCH_DETAILS).unwrap();
+        let mut sink = SqliteSink::new(Connection::open_in_memory().unwrap()).unwrap();
+
+        // writing a match twice replaces it
+        sink.write(&rows).unwrap();
+        sink.write(&rows).unwrap();
+
+        assert_eq!(count(&sink, "matches"), 1);
+        assert_eq!(count(&sink, "players"), 10);
+        assert_eq!(count(&sink, "bans"), 10);
+        assert!(count(&sink, "items") > 0);
+
+        let first_ban: String = sink
+            .connection()
+            .query_row("SELECT god_name FROM bans WHERE ban_order = 1", [], |x| x.get(0))
+            .unwrap();
+        assert_eq!(first_ban, "Ao Kuang");
+    }
+
+    test sqlite_skips_bad_matches() {
+        let mut rows: Vec<PlayerMatchDetails> = serde_json::from_str(test_responses::GET_MATCH_DETAILS).unwrap();
+        let mut broken = rows[0].clone();
+        broken.Match = Some(1);
+        broken.TaskForce = None;
+        rows.insert(0, broken);
+        let mut sink = SqliteSink::new(Connection::open_in_memory().unwrap()).unwrap();
+
+        sink.write(&rows).unwrap();
+        assert_eq!(count(&sink, "matches"), 1);
+        assert_eq!(count(&sink, "players"), 10);
+        assert_eq!(sink.skipped().len(), 1);
+        assert!(sink.skipped()[0].contains("no task force"));
+    }
+}
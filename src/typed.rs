@@ -0,0 +1,903 @@
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+use crate::hi_rez_constants::DataConstants;
+use crate::models;
+
+#[cfg(test)]
+use galvanic_test::test_suite;
+
+/*
+ * The models with idiomatic names and types, for code that would rather not deal with HiRez's
+ *
+ * Fields are snake_case, renamed to and from HiRez's names, so these parse the same replies the
+ * raw models in models do and serialize with the same field names. Values HiRez sends as
+ * strings become enums, and Entry_Datetime a DateTime. Values the enums don't know are kept
+ * as Other, so never fail a reply
+ *
+ * Known values are normalized rather than kept as sent: "Season 6 Conquest" is Map::Conquest
+ * and serializes as "Conquest", "EU" is Region::Europe and serializes as "Europe", and roles
+ * lose HiRez's padding. Use the raw models where the exact text matters
+ */
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum WinStatus {
+    Winner,
+    Loser,
+    Other(String),
+}
+
+impl From<String> for WinStatus {
+    fn from(status: String) -> WinStatus {
+        match status.trim() {
+            "Winner" => WinStatus::Winner,
+            "Loser" => WinStatus::Loser,
+            _ => WinStatus::Other(status),
+        }
+    }
+}
+
+impl From<WinStatus> for String {
+    fn from(status: WinStatus) -> String {
+        match status {
+            WinStatus::Winner => String::from("Winner"),
+            WinStatus::Loser => String::from("Loser"),
+            WinStatus::Other(status) => status,
+        }
+    }
+}
+
+// HiRez sends both the names and the short codes of regions
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum Region {
+    NorthAmerica,
+    Europe,
+    Brazil,
+    Oceania,
+    SoutheastAsia,
+    Other(String),
+}
+
+impl From<String> for Region {
+    fn from(region: String) -> Region {
+        match region.trim() {
+            "North America" | "NA" => Region::NorthAmerica,
+            "Europe" | "EU" => Region::Europe,
+            "Brazil" | "BR" => Region::Brazil,
+            "Australia" | "Oceania" | "OCE" => Region::Oceania,
+            "Southeast Asia" | "SEA" => Region::SoutheastAsia,
+            _ => Region::Other(region),
+        }
+    }
+}
+
+impl From<Region> for String {
+    fn from(region: Region) -> String {
+        match region {
+            Region::NorthAmerica => String::from("North America"),
+            Region::Europe => String::from("Europe"),
+            Region::Brazil => String::from("Brazil"),
+            Region::Oceania => String::from("Oceania"),
+            Region::SoutheastAsia => String::from("Southeast Asia"),
+            Region::Other(region) => region,
+        }
+    }
+}
+
+/*
+ * The map a match was played on, by game mode
+ *
+ * HiRez names maps after the season or event they are from, as in "Season 6 Conquest", which
+ * is left out here
+ */
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum Map {
+    Conquest,
+    Arena,
+    Joust,
+    Siege,
+    Assault,
+    Clash,
+    Slash,
+    Other(String),
+}
+
+impl From<String> for Map {
+    fn from(map: String) -> Map {
+        let lower = map.to_lowercase();
+        let modes = vec![
+            ("conquest", Map::Conquest),
+            ("arena", Map::Arena),
+            ("joust", Map::Joust),
+            ("siege", Map::Siege),
+            ("assault", Map::Assault),
+            ("clash", Map::Clash),
+            ("slash", Map::Slash),
+        ];
+        modes
+            .into_iter()
+            .find(|(mode, _)| lower.contains(mode))
+            .map(|(_, x)| x)
+            .unwrap_or(Map::Other(map))
+    }
+}
+
+impl From<Map> for String {
+    fn from(map: Map) -> String {
+        match map {
+            Map::Conquest => String::from("Conquest"),
+            Map::Arena => String::from("Arena"),
+            Map::Joust => String::from("Joust"),
+            Map::Siege => String::from("Siege"),
+            Map::Assault => String::from("Assault"),
+            Map::Clash => String::from("Clash"),
+            Map::Slash => String::from("Slash"),
+            Map::Other(map) => map,
+        }
+    }
+}
+
+// the side of the map a team plays on, 1 or 2 in HiRez's replies
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(from = "u8", into = "u8")]
+pub enum TaskForce {
+    Order,
+    Chaos,
+    // such as the 0 sent for matches without a winner
+    Other(u8),
+}
+
+impl From<u8> for TaskForce {
+    fn from(id: u8) -> TaskForce {
+        match id {
+            1 => TaskForce::Order,
+            2 => TaskForce::Chaos,
+            _ => TaskForce::Other(id),
+        }
+    }
+}
+
+impl From<TaskForce> for u8 {
+    fn from(task_force: TaskForce) -> u8 {
+        match task_force {
+            TaskForce::Order => 1,
+            TaskForce::Chaos => 2,
+            TaskForce::Other(id) => id,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum GodRole {
+    Assassin,
+    Guardian,
+    Hunter,
+    Mage,
+    Warrior,
+    Other(String),
+}
+
+impl From<String> for GodRole {
+    // HiRez pads roles with a leading space
+    fn from(role: String) -> GodRole {
+        match role.trim() {
+            "Assassin" => GodRole::Assassin,
+            "Guardian" => GodRole::Guardian,
+            "Hunter" => GodRole::Hunter,
+            "Mage" => GodRole::Mage,
+            "Warrior" => GodRole::Warrior,
+            _ => GodRole::Other(role),
+        }
+    }
+}
+
+impl From<GodRole> for String {
+    fn from(role: GodRole) -> String {
+        match role {
+            GodRole::Assassin => String::from("Assassin"),
+            GodRole::Guardian => String::from("Guardian"),
+            GodRole::Hunter => String::from("Hunter"),
+            GodRole::Mage => String::from("Mage"),
+            GodRole::Warrior => String::from("Warrior"),
+            GodRole::Other(role) => role,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum AttackRange {
+    Melee,
+    Ranged,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum PowerType {
+    Physical,
+    Magical,
+}
+
+// how a god attacks, sent by HiRez as e.g. "Ranged, Magical"
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum GodType {
+    Known { range: AttackRange, power: PowerType },
+    Other(String),
+}
+
+impl GodType {
+    pub fn range(&self) -> Option<AttackRange> {
+        match self {
+            GodType::Known { range, .. } => Some(*range),
+            GodType::Other(_) => None,
+        }
+    }
+
+    pub fn power(&self) -> Option<PowerType> {
+        match self {
+            GodType::Known { power, .. } => Some(*power),
+            GodType::Other(_) => None,
+        }
+    }
+}
+
+impl From<String> for GodType {
+    fn from(god_type: String) -> GodType {
+        let (mut range, mut power) = (None, None);
+        for part in god_type.split(',').map(|x| x.trim()) {
+            match part {
+                "Melee" => range = Some(AttackRange::Melee),
+                "Ranged" => range = Some(AttackRange::Ranged),
+                "Physical" => power = Some(PowerType::Physical),
+                "Magical" => power = Some(PowerType::Magical),
+                _ => {}
+            }
+        }
+        match (range, power) {
+            (Some(range), Some(power)) => GodType::Known { range, power },
+            _ => GodType::Other(god_type),
+        }
+    }
+}
+
+impl From<GodType> for String {
+    fn from(god_type: GodType) -> String {
+        match god_type {
+            GodType::Known { range, power } => format!("{:?}, {:?}", range, power),
+            GodType::Other(god_type) => god_type,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum ItemType {
+    Item,
+    Consumable,
+    Active,
+    Other(String),
+}
+
+impl From<String> for ItemType {
+    fn from(item_type: String) -> ItemType {
+        match item_type.trim() {
+            "Item" => ItemType::Item,
+            "Consumable" => ItemType::Consumable,
+            "Active" => ItemType::Active,
+            _ => ItemType::Other(item_type),
+        }
+    }
+}
+
+impl From<ItemType> for String {
+    fn from(item_type: ItemType) -> String {
+        match item_type {
+            ItemType::Item => String::from("Item"),
+            ItemType::Consumable => String::from("Consumable"),
+            ItemType::Active => String::from("Active"),
+            ItemType::Other(item_type) => item_type,
+        }
+    }
+}
+
+// HiRez's "y" and "n" flags
+mod flag {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<bool>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(flag) => match flag.trim() {
+                "y" | "Y" => Ok(Some(true)),
+                "n" | "N" => Ok(Some(false)),
+                "" => Ok(None),
+                _ => Err(serde::de::Error::custom(format!("Invalid flag {}", flag))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    pub fn serialize<S: Serializer>(flag: &Option<bool>, serializer: S) -> Result<S::Ok, S::Error> {
+        match flag {
+            Some(true) => serializer.serialize_str("y"),
+            Some(false) => serializer.serialize_str("n"),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+// timestamps in the format of Entry_Datetime, e.g. "8/5/2019 12:06:23 AM"
+mod server_timestamp {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::clock::{format_server_timestamp, parse_server_timestamp};
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DateTime<Utc>>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(timestamp) => parse_server_timestamp(&timestamp)
+                .map(Some)
+                .map_err(serde::de::Error::custom),
+            None => Ok(None),
+        }
+    }
+
+    pub fn serialize<S: Serializer>(
+        timestamp: &Option<DateTime<Utc>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match timestamp {
+            Some(timestamp) => serializer.serialize_str(&format_server_timestamp(timestamp)),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GetMatchIdsByQueueReply {
+    pub ret_msg: Option<String>,
+    #[serde(rename = "Active_Flag", default, with = "flag")]
+    pub active: Option<bool>,
+    #[serde(rename = "Match")]
+    pub match_id: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MergedPlayer {
+    pub merge_datetime: String,
+    #[serde(rename = "playerId")]
+    pub player_id: String,
+    #[serde(rename = "portalId")]
+    pub portal_id: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PlayerMatchDetails {
+    #[serde(rename = "Account_Level")]
+    pub account_level: Option<u16>,
+    #[serde(rename = "ActiveId1")]
+    pub active_id1: Option<u16>,
+    #[serde(rename = "ActiveId2")]
+    pub active_id2: Option<u16>,
+    #[serde(rename = "ActiveId3")]
+    pub active_id3: Option<u16>,
+    #[serde(rename = "ActiveId4")]
+    pub active_id4: Option<u16>,
+    #[serde(rename = "ActivePlayerId")]
+    pub active_player_id: Option<String>,
+    #[serde(rename = "Assists")]
+    pub assists: Option<u16>,
+    #[serde(rename = "Ban1")]
+    pub ban1: Option<String>,
+    #[serde(rename = "Ban10")]
+    pub ban10: Option<String>,
+    #[serde(rename = "Ban10Id")]
+    pub ban10_id: Option<u16>,
+    #[serde(rename = "Ban1Id")]
+    pub ban1_id: Option<u16>,
+    #[serde(rename = "Ban2")]
+    pub ban2: Option<String>,
+    #[serde(rename = "Ban2Id")]
+    pub ban2_id: Option<u16>,
+    #[serde(rename = "Ban3")]
+    pub ban3: Option<String>,
+    #[serde(rename = "Ban3Id")]
+    pub ban3_id: Option<u16>,
+    #[serde(rename = "Ban4")]
+    pub ban4: Option<String>,
+    #[serde(rename = "Ban4Id")]
+    pub ban4_id: Option<u16>,
+    #[serde(rename = "Ban5")]
+    pub ban5: Option<String>,
+    #[serde(rename = "Ban5Id")]
+    pub ban5_id: Option<u16>,
+    #[serde(rename = "Ban6")]
+    pub ban6: Option<String>,
+    #[serde(rename = "Ban6Id")]
+    pub ban6_id: Option<u16>,
+    #[serde(rename = "Ban7")]
+    pub ban7: Option<String>,
+    #[serde(rename = "Ban7Id")]
+    pub ban7_id: Option<u16>,
+    #[serde(rename = "Ban8")]
+    pub ban8: Option<String>,
+    #[serde(rename = "Ban8Id")]
+    pub ban8_id: Option<u16>,
+    #[serde(rename = "Ban9")]
+    pub ban9: Option<String>,
+    #[serde(rename = "Ban9Id")]
+    pub ban9_id: Option<u16>,
+    #[serde(rename = "Camps_Cleared")]
+    pub camps_cleared: Option<u16>,
+    #[serde(rename = "Conquest_Losses")]
+    pub conquest_losses: Option<u16>,
+    #[serde(rename = "Conquest_Points")]
+    pub conquest_points: Option<u16>,
+    #[serde(rename = "Conquest_Tier")]
+    pub conquest_tier: Option<u8>,
+    #[serde(rename = "Conquest_Wins")]
+    pub conquest_wins: Option<u16>,
+    #[serde(rename = "Damage_Bot")]
+    pub damage_bot: Option<u32>,
+    #[serde(rename = "Damage_Done_In_Hand")]
+    pub damage_done_in_hand: Option<u32>,
+    #[serde(rename = "Damage_Done_Magical")]
+    pub damage_done_magical: Option<u32>,
+    #[serde(rename = "Damage_Done_Physical")]
+    pub damage_done_physical: Option<u32>,
+    #[serde(rename = "Damage_Mitigated")]
+    pub damage_mitigated: Option<u32>,
+    #[serde(rename = "Damage_Player")]
+    pub damage_player: Option<u32>,
+    #[serde(rename = "Damage_Taken")]
+    pub damage_taken: Option<u32>,
+    #[serde(rename = "Damage_Taken_Magical")]
+    pub damage_taken_magical: Option<u32>,
+    #[serde(rename = "Damage_Taken_Physical")]
+    pub damage_taken_physical: Option<u32>,
+    #[serde(rename = "Deaths")]
+    pub deaths: Option<u16>,
+    #[serde(rename = "Distance_Traveled")]
+    pub distance_traveled: Option<u32>,
+    #[serde(rename = "Duel_Losses")]
+    pub duel_losses: Option<u16>,
+    #[serde(rename = "Duel_Points")]
+    pub duel_points: Option<u16>,
+    #[serde(rename = "Duel_Tier")]
+    pub duel_tier: Option<u8>,
+    #[serde(rename = "Duel_Wins")]
+    pub duel_wins: Option<u16>,
+    #[serde(rename = "Entry_Datetime", default, with = "server_timestamp")]
+    pub entry_datetime: Option<DateTime<Utc>>,
+    #[serde(rename = "Final_Match_Level")]
+    pub final_match_level: Option<u16>,
+    #[serde(rename = "First_Ban_Side")]
+    pub first_ban_side: Option<String>,
+    #[serde(rename = "GodId")]
+    pub god_id: Option<u16>,
+    #[serde(rename = "Gold_Earned")]
+    pub gold_earned: Option<u32>,
+    #[serde(rename = "Gold_Per_Minute")]
+    pub gold_per_minute: Option<u16>,
+    #[serde(rename = "Healing")]
+    pub healing: Option<u32>,
+    #[serde(rename = "Healing_Bot")]
+    pub healing_bot: Option<u32>,
+    #[serde(rename = "Healing_Player_Self")]
+    pub healing_player_self: Option<u32>,
+    #[serde(rename = "ItemId1")]
+    pub item_id1: Option<u16>,
+    #[serde(rename = "ItemId2")]
+    pub item_id2: Option<u16>,
+    #[serde(rename = "ItemId3")]
+    pub item_id3: Option<u16>,
+    #[serde(rename = "ItemId4")]
+    pub item_id4: Option<u16>,
+    #[serde(rename = "ItemId5")]
+    pub item_id5: Option<u16>,
+    #[serde(rename = "ItemId6")]
+    pub item_id6: Option<u16>,
+    #[serde(rename = "Item_Active_1")]
+    pub item_active_1: Option<String>,
+    #[serde(rename = "Item_Active_2")]
+    pub item_active_2: Option<String>,
+    #[serde(rename = "Item_Active_3")]
+    pub item_active_3: Option<String>,
+    #[serde(rename = "Item_Active_4")]
+    pub item_active_4: Option<String>,
+    #[serde(rename = "Item_Purch_1")]
+    pub item_purch_1: Option<String>,
+    #[serde(rename = "Item_Purch_2")]
+    pub item_purch_2: Option<String>,
+    #[serde(rename = "Item_Purch_3")]
+    pub item_purch_3: Option<String>,
+    #[serde(rename = "Item_Purch_4")]
+    pub item_purch_4: Option<String>,
+    #[serde(rename = "Item_Purch_5")]
+    pub item_purch_5: Option<String>,
+    #[serde(rename = "Item_Purch_6")]
+    pub item_purch_6: Option<String>,
+    #[serde(rename = "Joust_Losses")]
+    pub joust_losses: Option<u16>,
+    #[serde(rename = "Joust_Points")]
+    pub joust_points: Option<u16>,
+    #[serde(rename = "Joust_Tier")]
+    pub joust_tier: Option<u8>,
+    #[serde(rename = "Joust_Wins")]
+    pub joust_wins: Option<u16>,
+    #[serde(rename = "Killing_Spree")]
+    pub killing_spree: Option<u16>,
+    #[serde(rename = "Kills_Bot")]
+    pub kills_bot: Option<u32>,
+    #[serde(rename = "Kills_Double")]
+    pub kills_double: Option<u16>,
+    #[serde(rename = "Kills_Fire_Giant")]
+    pub kills_fire_giant: Option<u16>,
+    #[serde(rename = "Kills_First_Blood")]
+    pub kills_first_blood: Option<u16>,
+    #[serde(rename = "Kills_Gold_Fury")]
+    pub kills_gold_fury: Option<u16>,
+    #[serde(rename = "Kills_Penta")]
+    pub kills_penta: Option<u16>,
+    #[serde(rename = "Kills_Phoenix")]
+    pub kills_phoenix: Option<u16>,
+    #[serde(rename = "Kills_Player")]
+    pub kills_player: Option<u16>,
+    #[serde(rename = "Kills_Quadra")]
+    pub kills_quadra: Option<u16>,
+    #[serde(rename = "Kills_Siege_Juggernaut")]
+    pub kills_siege_juggernaut: Option<u16>,
+    #[serde(rename = "Kills_Single")]
+    pub kills_single: Option<u16>,
+    #[serde(rename = "Kills_Triple")]
+    pub kills_triple: Option<u16>,
+    #[serde(rename = "Kills_Wild_Juggernaut")]
+    pub kills_wild_juggernaut: Option<u16>,
+    #[serde(rename = "Map_Game")]
+    pub map: Option<Map>,
+    #[serde(rename = "Mastery_Level")]
+    pub mastery_level: Option<u16>,
+    #[serde(rename = "Match")]
+    pub match_id: Option<u32>,
+    #[serde(rename = "Match_Duration")]
+    pub match_duration: Option<u64>,
+    #[serde(
+        rename = "MergedPlayers",
+        default,
+        deserialize_with = "crate::decode::list"
+    )]
+    pub merged_players: Option<Vec<MergedPlayer>>,
+    #[serde(rename = "Minutes")]
+    pub minutes: Option<u16>,
+    #[serde(rename = "Multi_kill_Max")]
+    pub multi_kill_max: Option<u16>,
+    #[serde(rename = "Objective_Assists")]
+    pub objective_assists: Option<u16>,
+    #[serde(rename = "PartyId")]
+    pub party_id: Option<u32>,
+    #[serde(rename = "Rank_Stat_Conquest")]
+    pub rank_stat_conquest: Option<f32>,
+    #[serde(rename = "Rank_Stat_Duel")]
+    pub rank_stat_duel: Option<f32>,
+    #[serde(rename = "Rank_Stat_Joust")]
+    pub rank_stat_joust: Option<f32>,
+    #[serde(rename = "Reference_Name")]
+    pub reference_name: Option<String>,
+    #[serde(rename = "Region")]
+    pub region: Option<Region>,
+    #[serde(rename = "Skin")]
+    pub skin: Option<String>,
+    #[serde(rename = "SkinId")]
+    pub skin_id: Option<u16>,
+    #[serde(rename = "Structure_Damage")]
+    pub structure_damage: Option<u32>,
+    #[serde(rename = "Surrendered")]
+    pub surrendered: Option<u8>,
+    #[serde(rename = "TaskForce")]
+    pub task_force: Option<TaskForce>,
+    #[serde(rename = "Team1Score")]
+    pub team1_score: Option<u64>,
+    #[serde(rename = "Team2Score")]
+    pub team2_score: Option<u64>,
+    #[serde(rename = "TeamId")]
+    pub team_id: Option<u32>,
+    #[serde(rename = "Team_Name")]
+    pub team_name: Option<String>,
+    #[serde(rename = "Time_In_Match_Seconds")]
+    pub time_in_match_seconds: Option<u16>,
+    #[serde(rename = "Towers_Destroyed")]
+    pub towers_destroyed: Option<u16>,
+    #[serde(rename = "Wards_Placed")]
+    pub wards_placed: Option<u32>,
+    #[serde(rename = "Win_Status")]
+    pub win_status: Option<WinStatus>,
+    #[serde(rename = "Winning_TaskForce")]
+    pub winning_task_force: Option<TaskForce>,
+    #[serde(rename = "hasReplay", default, with = "flag")]
+    pub has_replay: Option<bool>,
+    pub hz_gamer_tag: Option<String>,
+    pub hz_player_name: Option<String>,
+    pub match_queue_id: Option<u16>,
+    pub name: Option<String>,
+    #[serde(rename = "playerId")]
+    pub player_id: Option<String>,
+    #[serde(rename = "playerName")]
+    pub player_name: Option<String>,
+    #[serde(rename = "playerPortalId")]
+    pub player_portal_id: Option<String>,
+    #[serde(rename = "playerPortalUserId")]
+    pub player_portal_user_id: Option<String>,
+    pub ret_msg: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AbilityDescriptionValuePair {
+    pub description: Option<String>,
+    pub value: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ItemDescriptionValuePair {
+    #[serde(rename = "Description")]
+    pub description: Option<String>,
+    #[serde(rename = "Value")]
+    pub value: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AbilityItemDescription {
+    pub cooldown: Option<String>,
+    pub cost: Option<String>,
+    #[serde(
+        rename = "menuitems",
+        default,
+        deserialize_with = "crate::decode::list"
+    )]
+    pub menu_items: Option<Vec<AbilityDescriptionValuePair>>,
+    #[serde(
+        rename = "rankitems",
+        default,
+        deserialize_with = "crate::decode::list"
+    )]
+    pub rank_items: Option<Vec<AbilityDescriptionValuePair>>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GodAbilityDescription {
+    #[serde(rename = "itemDescription")]
+    pub item_description: Option<AbilityItemDescription>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GodAbility {
+    #[serde(rename = "Description")]
+    pub description: Option<GodAbilityDescription>,
+    #[serde(rename = "Id")]
+    pub id: Option<u16>,
+    #[serde(rename = "Summary")]
+    pub summary: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct God {
+    #[serde(rename = "Ability_1")]
+    pub ability_1: Option<GodAbility>,
+    #[serde(rename = "Ability_2")]
+    pub ability_2: Option<GodAbility>,
+    #[serde(rename = "Ability_3")]
+    pub ability_3: Option<GodAbility>,
+    #[serde(rename = "Ability_4")]
+    pub ability_4: Option<GodAbility>,
+    #[serde(rename = "Ability_5")]
+    pub ability_5: Option<GodAbility>,
+    #[serde(rename = "basicAttack")]
+    pub basic_attack: Option<ItemDescription>,
+    #[serde(rename = "AttackSpeed")]
+    pub attack_speed: Option<f32>,
+    #[serde(rename = "AttackSpeedPerLevel")]
+    pub attack_speed_per_level: Option<f32>,
+    #[serde(rename = "Cons")]
+    pub cons: Option<String>,
+    #[serde(rename = "HP5PerLevel")]
+    pub hp5_per_level: Option<f32>,
+    #[serde(rename = "Health")]
+    pub health: Option<u16>,
+    #[serde(rename = "HealthPerFive")]
+    pub health_per_five: Option<u16>,
+    #[serde(rename = "HealthPerLevel")]
+    pub health_per_level: Option<u8>,
+    #[serde(rename = "MP5PerLevel")]
+    pub mp5_per_level: Option<f32>,
+    #[serde(rename = "MagicProtection")]
+    pub magic_protection: Option<u8>,
+    #[serde(rename = "MagicProtectionPerLevel")]
+    pub magic_protection_per_level: Option<f32>,
+    #[serde(rename = "MagicalPower")]
+    pub magical_power: Option<u8>,
+    #[serde(rename = "MagicalPowerPerLevel")]
+    pub magical_power_per_level: Option<f32>,
+    #[serde(rename = "Mana")]
+    pub mana: Option<u16>,
+    #[serde(rename = "ManaPerFive")]
+    pub mana_per_five: Option<f32>,
+    #[serde(rename = "ManaPerLevel")]
+    pub mana_per_level: Option<u8>,
+    #[serde(rename = "Name")]
+    pub name: Option<String>,
+    #[serde(rename = "PhysicalPower")]
+    pub physical_power: Option<u8>,
+    #[serde(rename = "PhysicalPowerPerLevel")]
+    pub physical_power_per_level: Option<f32>,
+    #[serde(rename = "PhysicalProtection")]
+    pub physical_protection: Option<u8>,
+    #[serde(rename = "PhysicalProtectionPerLevel")]
+    pub physical_protection_per_level: Option<f32>,
+    #[serde(rename = "Pros")]
+    pub pros: Option<String>,
+    #[serde(rename = "Roles")]
+    pub role: Option<GodRole>,
+    #[serde(rename = "Speed")]
+    pub speed: Option<u16>,
+    #[serde(rename = "Type")]
+    pub god_type: Option<GodType>,
+    pub id: Option<u16>,
+    pub ret_msg: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ItemDescription {
+    #[serde(rename = "Description")]
+    pub description: Option<String>,
+    #[serde(
+        rename = "MenuItems",
+        default,
+        deserialize_with = "crate::decode::list"
+    )]
+    pub menu_items: Option<Vec<ItemDescriptionValuePair>>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Item {
+    #[serde(rename = "DeviceName")]
+    pub device_name: String,
+    #[serde(rename = "ItemId")]
+    pub item_id: Option<u16>,
+    #[serde(rename = "ItemTier")]
+    pub item_tier: Option<u8>,
+    #[serde(rename = "Price")]
+    pub price: Option<u16>,
+    #[serde(rename = "RestrictedRoles")]
+    pub restricted_roles: Option<String>,
+    #[serde(rename = "Type")]
+    pub item_type: Option<ItemType>,
+    pub ret_msg: Option<String>,
+}
+
+impl PlayerMatchDetails {
+    // None if the queue id is missing or one HiRust doesn't know
+    pub fn queue(&self) -> Option<DataConstants> {
+        self.match_queue_id
+            .and_then(|x| DataConstants::try_from(x).ok())
+    }
+}
+
+// goes through HiRez's field names, which both models serialize to
+fn convert<R: Serialize, T: DeserializeOwned>(raw: &R) -> Result<T, String> {
+    serde_json::to_value(raw)
+        .and_then(serde_json::from_value)
+        .map_err(|msg| msg.to_string())
+}
+
+impl TryFrom<&models::GetMatchIdsByQueueReply> for GetMatchIdsByQueueReply {
+    type Error = String;
+
+    fn try_from(raw: &models::GetMatchIdsByQueueReply) -> Result<GetMatchIdsByQueueReply, String> {
+        convert(raw)
+    }
+}
+
+impl TryFrom<&models::PlayerMatchDetails> for PlayerMatchDetails {
+    type Error = String;
+
+    fn try_from(raw: &models::PlayerMatchDetails) -> Result<PlayerMatchDetails, String> {
+        convert(raw)
+    }
+}
+
+impl TryFrom<&models::God> for God {
+    type Error = String;
+
+    fn try_from(raw: &models::God) -> Result<God, String> {
+        convert(raw)
+    }
+}
+
+impl TryFrom<&models::Item> for Item {
+    type Error = String;
+
+    fn try_from(raw: &models::Item) -> Result<Item, String> {
+        convert(raw)
+    }
+}
+
+#[cfg(test)]
+test_suite! {
+    name test_typed;
+    use super::*;
+    use chrono::TimeZone;
+    use crate::decode::decode_lenient;
+    use crate::hi_rez_constants::ReturnDataType;
+    use crate::test_responses;
+
+    test match_details() {
+        let rows: Vec<PlayerMatchDetails> = serde_json::from_str(test_responses::GET_MATCH_DETAILS).unwrap();
+        let row = &rows[0];
+
+        assert_eq!(row.match_id, Some(956598608));
+        assert_eq!(row.queue(), Some(DataConstants::RankedConquest));
+        assert_eq!(row.map, Some(Map::Conquest));
+        assert_eq!(row.region, Some(Region::Europe));
+        assert_eq!(row.entry_datetime, Some(Utc.with_ymd_and_hms(2019, 8, 5, 0, 6, 23).unwrap()));
+        assert_eq!(row.has_replay, Some(false));
+        let mut winners = rows.iter().filter(|x| x.win_status == Some(WinStatus::Winner));
+        assert!(winners.all(|x| x.task_force == x.winning_task_force && x.task_force.is_some()));
+
+        let value = serde_json::to_value(row).unwrap();
+        assert_eq!(value["Entry_Datetime"], "8/5/2019 12:06:23 AM");
+        assert_eq!(value["TaskForce"], u8::from(row.task_force.unwrap()));
+    }
+
+    test from_raw_models() {
+        let raw: Vec<models::PlayerMatchDetails> = serde_json::from_str(test_responses::GET_MATCH_DETAILS).unwrap();
+        let row = PlayerMatchDetails::try_from(&raw[0]).unwrap();
+        assert_eq!(row.hz_player_name, raw[0].hz_player_name);
+        assert_eq!(row.ban3_id, raw[0].Ban3Id);
+
+        let ids: Vec<models::GetMatchIdsByQueueReply> = serde_json::from_str(test_responses::GET_MATCH_IDS_BY_QUEUE).unwrap();
+        let id = GetMatchIdsByQueueReply::try_from(&ids[0]).unwrap();
+        assert_eq!(id.active, Some(false));
+        assert_eq!(id.match_id, ids[0].Match);
+    }
+
+    test gods_and_items() {
+        let gods: Vec<God> = serde_json::from_str(
+            "[{ \"Name\": \"Anubis\", \"Roles\": \" Mage\", \"Type\": \" Ranged, Magical\" },
+              { \"Name\": \"Nobody\", \"Roles\": \" Jungler\", \"Type\": \"Hovering\" }]",
+        )
+        .unwrap();
+        assert_eq!(gods[0].role, Some(GodRole::Mage));
+        assert_eq!(gods[0].god_type, Some(GodType::Known { range: AttackRange::Ranged, power: PowerType::Magical }));
+        assert_eq!(gods[1].role, Some(GodRole::Other(String::from(" Jungler"))));
+        assert_eq!(gods[1].god_type.as_ref().unwrap().range(), None);
+        assert_eq!(serde_json::to_value(&gods[1]).unwrap()["Type"], "Hovering");
+
+        let items: Vec<Item> = serde_json::from_str("[{ \"DeviceName\": \"Blink Rune\", \"Type\": \"Active\" }]").unwrap();
+        assert_eq!(items[0].device_name, "Blink Rune");
+        assert_eq!(items[0].item_type, Some(ItemType::Active));
+    }
+
+    test unknown_task_forces_are_kept() {
+        let rows: Vec<PlayerMatchDetails> = serde_json::from_str("[{ \"Match\": 1, \"Winning_TaskForce\": 0 }]").unwrap();
+        assert_eq!(rows[0].winning_task_force, Some(TaskForce::Other(0)));
+        assert_eq!(serde_json::to_value(&rows[0]).unwrap()["Winning_TaskForce"], 0);
+    }
+
+    test invalid_values_are_anomalies() {
+        let (rows, anomalies) = decode_lenient::<Vec<PlayerMatchDetails>>(
+            &ReturnDataType::Json,
+            "[{ \"Match\": 1, \"Entry_Datetime\": \"yesterday\" }]",
+        )
+        .unwrap();
+        assert_eq!(rows[0].entry_datetime, None);
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].field, "Entry_Datetime");
+    }
+}
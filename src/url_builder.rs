@@ -1,16 +1,19 @@
-use chrono::{Datelike, Timelike, Utc};
+use chrono::{DateTime, Datelike, Timelike, Utc};
 use crypto::{digest::Digest, md5::Md5};
 
+use crate::clock::Clock;
 use crate::hi_rez_constants::{ReturnDataType, UrlConstants};
 
-fn build_signature(id: &str, method_name: &UrlConstants, key: &str, date: &str) -> String {
+#[cfg(test)]
+use galvanic_test::test_suite;
+
+fn build_signature(id: &str, method_name: &str, key: &str, date: &str) -> String {
     let mut md5 = Md5::new();
-    md5.input_str(&format!("{}{}{}{}", id, method_name.val(), key, date));
+    md5.input_str(&format!("{}{}{}{}", id, method_name, key, date));
     return md5.result_str();
 }
 
-fn get_timestamp() -> String {
-    let systemtime = Utc::now();
+fn format_timestamp(systemtime: DateTime<Utc>) -> String {
     let timestamp: String = format!(
         "{}{:02}{:02}{:02}{:02}{:02}",
         systemtime.year(),
@@ -33,10 +36,11 @@ pub fn session_url(
     data_type: &ReturnDataType,
     id: &str,
     key: &str,
+    clock: &dyn Clock,
 ) -> String {
     let method_name = UrlConstants::CreateSession;
-    let timestamp: String = get_timestamp();
-    let signature: String = build_signature(id, &method_name, key, &timestamp);
+    let timestamp: String = format_timestamp(clock.now());
+    let signature: String = build_signature(id, &method_name.val(), key, &timestamp);
     return format!(
         "{}/{}{}/{}/{}/{}",
         base_url.val(),
@@ -48,21 +52,23 @@ pub fn session_url(
     );
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn url(
     id: &String,
     key: &String,
     session: &String,
     base_url: &UrlConstants,
-    method_name: &UrlConstants,
+    method_name: &str,
     data_type: &ReturnDataType,
     method_specific: &String,
+    clock: &dyn Clock,
 ) -> String {
-    let timestamp: String = get_timestamp();
+    let timestamp: String = format_timestamp(clock.now());
     let signature: String = build_signature(id, method_name, key, &timestamp);
     return format!(
         "{}/{}{}/{}/{}/{}/{}{}",
         base_url.val(),
-        method_name.val(),
+        method_name,
         data_type.val(),
         id,
         signature,
@@ -71,3 +77,20 @@ pub fn url(
         method_specific,
     );
 }
+
+#[cfg(test)]
+test_suite! {
+    name test_url_builder;
+    use super::*;
+    use chrono::TimeZone;
+    use crate::clock::FixedClock;
+
+    test session_url_signed_with_clock() {
+        let clock = FixedClock::new(Utc.with_ymd_and_hms(2019, 8, 10, 12, 0, 0).unwrap());
+        let url = session_url(&UrlConstants::UrlBase, &ReturnDataType::Json, "1004", "ABCDEF", &clock);
+        assert_eq!(
+            url,
+            "https://api.smitegame.com/smiteapi.svc/createsessionjson/1004/41c11e2e63459d6e723b58ead99d6a1b/20190810120000"
+        );
+    }
+}
@@ -3,18 +3,19 @@ use galvanic_test::test_suite;
 test_suite! {
     name integration_test_api;
     use hirust::api::SmiteAPI;
+    use hirust::hi_rez_constants::Language;
 
     #[ignore]
     test get_gods() {
-        let api = SmiteAPI::new("../hirez-dev-credentials.txt".to_string());
-        let gods = api.get_gods();
+        let api = SmiteAPI::new("../hirez-dev-credentials.txt".to_string()).unwrap();
+        let gods = api.get_gods(Language::English);
         assert_eq!(gods.len(), 104);
     }
 
     #[ignore]
     test get_items() {
-        let api = SmiteAPI::new("../hirez-dev-credentials.txt".to_string());
-        let gods = api.get_items();
+        let api = SmiteAPI::new("../hirez-dev-credentials.txt".to_string()).unwrap();
+        let gods = api.get_items(Language::English);
         assert_eq!(gods.len(), 272);
     }
 }
\ No newline at end of file
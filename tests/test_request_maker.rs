@@ -1,6 +1,7 @@
-use chrono::{TimeZone, Utc};
+use chrono::NaiveDate;
 use galvanic_test::test_suite;
 
+use hirust::endpoints::TimeWindow;
 use hirust::hi_rez_constants::{DataConstants, UrlConstants};
 use hirust::request_maker::{GetMatchIdsByQueueRequest, RequestMaker};
 use hirust::session_manager::{Auth, SessionManager};
@@ -11,15 +12,14 @@ test_suite! {
 
     #[ignore]
     test bulk_pull() {
-        let auth = Auth::from_file("../hirez-dev-credentials.txt");
+        let auth = Auth::from_file("../hirez-dev-credentials.txt").unwrap();
         let session_manager = SessionManager::new(auth, UrlConstants::UrlBase);
         let mut request_maker = RequestMaker::new(session_manager);
 
         let ids = request_maker.get_match_ids_by_queue(vec![GetMatchIdsByQueueRequest {
             queue_id: DataConstants::RankedConquest,
-            date: Utc.ymd(2019, 8, 10),
-            hour: String::from("-1"),
-            minute: String::from(""),
+            date: NaiveDate::from_ymd_opt(2019, 8, 10).unwrap(),
+            window: TimeWindow::WholeDay,
         }]).unwrap();
 
         let match_details = request_maker.get_match_details(ids);